@@ -0,0 +1,53 @@
+//! Compares serial and parallel processing of inputs of different sizes.
+//!
+//! Run with `cargo bench --bench parallel`. The parallel path uses as
+//! many threads as there are cores unless the number of threads is
+//! given in the `THREADS` environment variable.
+
+use std::time::{Duration, Instant};
+
+use tx_engine::{process_parallel, process_reader};
+
+/// Generates an input with given number of deposits spread across 1000
+/// clients, every tenth of them disputed.
+fn input(records: usize) -> String {
+    let mut input = String::from("type,client,tx,amount\n");
+    for tx in 0..records {
+        let client = tx % 1000;
+        input += &format!("deposit,{client},{tx},1.2345\n");
+        if tx % 10 == 0 {
+            input += &format!("dispute,{client},{tx},\n");
+        }
+    }
+    input
+}
+
+/// Returns the fastest of several runs of `f`.
+fn measure(mut f: impl FnMut()) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .expect("at least one run")
+}
+
+fn main() {
+    let threads = std::env::var("THREADS")
+        .ok()
+        .and_then(|threads| threads.parse().ok())
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, usize::from));
+    println!("records,serial,parallel ({threads} threads)");
+    for records in [1_000, 10_000, 100_000, 1_000_000] {
+        let input = input(records);
+        let serial = measure(|| {
+            process_reader(input.as_bytes()).expect("processing to succeed");
+        });
+        let parallel = measure(|| {
+            process_parallel(input.as_bytes(), threads).expect("processing to succeed");
+        });
+        println!("{records},{serial:?},{parallel:?}");
+    }
+}
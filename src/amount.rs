@@ -0,0 +1,66 @@
+//! Numeric types of amounts of funds.
+
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+/// Numeric type of funds held in [`AmountsOf`](crate::AmountsOf).
+///
+/// The engine uses [`Decimal`] by default which represents amounts
+/// exactly. The implementation for `f64` trades exactness for cheaper
+/// arithmetic and can be used with [`Engine<f64>`](crate::Engine) when
+/// only the balance rules are needed.
+///
+/// The default value must be equal to [`Amount::ZERO`] since it is used
+/// for funds of new accounts.
+pub trait Amount: Copy + Default + PartialOrd {
+    /// Amount equal to zero.
+    const ZERO: Self;
+
+    /// Converts the amount of a record returning `None` if it cannot be
+    /// represented.
+    ///
+    /// Amounts are always parsed and validated as [`Decimal`] thus the
+    /// conversion happens after checking their precision.
+    fn from_decimal(amount: Decimal) -> Option<Self>;
+
+    /// Adds two amounts returning `None` if the sum cannot be
+    /// represented.
+    fn checked_add(self, other: Self) -> Option<Self>;
+
+    /// Subtracts two amounts returning `None` if the difference cannot
+    /// be represented.
+    fn checked_sub(self, other: Self) -> Option<Self>;
+}
+
+impl Amount for Decimal {
+    const ZERO: Self = Decimal::ZERO;
+
+    fn from_decimal(amount: Decimal) -> Option<Self> {
+        Some(amount)
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Decimal::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        Decimal::checked_sub(self, other)
+    }
+}
+
+impl Amount for f64 {
+    const ZERO: Self = 0.0;
+
+    fn from_decimal(amount: Decimal) -> Option<Self> {
+        amount.to_f64()
+    }
+
+    /// Returns `None` if the sum is infinite or not a number.
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Some(self + other).filter(|sum| sum.is_finite())
+    }
+
+    /// Returns `None` if the difference is infinite or not a number.
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        Some(self - other).filter(|difference| difference.is_finite())
+    }
+}
@@ -0,0 +1,107 @@
+//! Processing of asynchronous inputs.
+
+use std::collections::HashMap;
+
+use csv::StringRecord;
+use futures::{Stream, StreamExt};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use crate::{
+    check_headers, invalid_field, Account, ClientId, Engine, EngineConfig, Error, ProcessOptions,
+    Record, Result, COLUMNS,
+};
+
+/// Process CSV data coming from an asynchronous reader.
+///
+/// This works the same way as [`process_reader`](crate::process_reader)
+/// but does not block the current thread while waiting for the input.
+/// The values will be stripped of whitespace.
+///
+/// The input is parsed line by line thus quoted values spanning
+/// multiple lines are not supported.
+pub async fn process_async<R: AsyncRead + Unpin>(reader: R) -> Result<HashMap<ClientId, Account>> {
+    process_async_with(reader, &ProcessOptions::default()).await
+}
+
+/// Process CSV data coming from an asynchronous reader using given
+/// options.
+///
+/// Works like [`process_async`]. The records are always applied in the
+/// order of the input, i.e. [`EngineConfig::two_pass`] and
+/// [`EngineConfig::skip_unknown_types`] are not supported.
+pub async fn process_async_with<R: AsyncRead + Unpin>(
+    reader: R,
+    options: &ProcessOptions,
+) -> Result<HashMap<ClientId, Account>> {
+    let mut lines = BufReader::new(reader).lines();
+    let mut engine = Engine::with_options(options.clone());
+    let mut headers = (!engine.config.has_headers).then(|| StringRecord::from(COLUMNS.as_slice()));
+    let mut number = 0;
+    let mut row = 0;
+    while let Some(line) = lines.next_line().await? {
+        number += 1;
+        let Some(fields) = parse_line(&line, &engine.config).map_err(|source| Error::Parse {
+            line: number,
+            row: row + u64::from(headers.is_some()),
+            source,
+        })?
+        else {
+            // empty lines are skipped just like the CSV reader does
+            continue;
+        };
+        match &headers {
+            None => {
+                headers = Some(check_headers(
+                    &fields,
+                    engine.config.reject_unknown_columns,
+                )?)
+            }
+            Some(headers) => {
+                row += 1;
+                let record = fields
+                    .deserialize::<Record>(Some(headers))
+                    .map_err(|source| {
+                        invalid_field(&source, &fields, headers, row).unwrap_or(Error::Parse {
+                            line: number,
+                            row,
+                            source,
+                        })
+                    })?;
+                engine.row = row;
+                engine.apply(record).map_err(|error| Error::AtRow {
+                    row,
+                    byte: None,
+                    source: Box::new(error),
+                })?
+            }
+        }
+    }
+    Ok(engine.into_accounts())
+}
+
+/// Process records coming from an asynchronous stream.
+///
+/// The records are applied in order as soon as they arrive, e.g. from
+/// a message queue. The first error, either coming from the stream or
+/// from applying the record, stops the processing.
+pub async fn process_stream<S>(stream: S) -> Result<HashMap<ClientId, Account>>
+where
+    S: Stream<Item = Result<Record>>,
+{
+    let mut stream = std::pin::pin!(stream);
+    let mut engine = Engine::new();
+    while let Some(record) = stream.next().await {
+        engine.apply(record?)?;
+    }
+    Ok(engine.into_accounts())
+}
+
+/// Parses a single line of CSV returning `None` if the line is empty.
+fn parse_line(line: &str, config: &EngineConfig) -> csv::Result<Option<StringRecord>> {
+    let mut rdr = config
+        .reader_builder()
+        .has_headers(false)
+        .from_reader(line.as_bytes());
+    let mut record = StringRecord::new();
+    Ok(rdr.read_record(&mut record)?.then_some(record))
+}
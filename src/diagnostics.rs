@@ -0,0 +1,77 @@
+//! Reporting of rejections and other notable events.
+
+use std::{fmt::Arguments, sync::Mutex};
+
+use crate::Rejection;
+
+/// Receiver of the diagnostics of the engine.
+///
+/// The engine reports rejected records (see [`Rejection`]) and notes
+/// about other notable events, e.g. ignored amounts of disputes. By
+/// default they are logged with [`LogDiagnostics`]. A different
+/// implementation, e.g. forwarding them to `tracing`, can be set with
+/// [`ProcessOptions::diagnostics`](crate::ProcessOptions::diagnostics).
+pub trait Diagnostics: std::fmt::Debug + Send + Sync {
+    /// Reports a rejected record.
+    fn rejection(&self, rejection: &Rejection);
+
+    /// Reports a notable event which is not a rejection.
+    fn note(&self, message: Arguments<'_>);
+}
+
+/// Diagnostics logged using the `log` crate.
+///
+/// Rejections are logged at the info level, notes at the warn level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogDiagnostics;
+
+impl Diagnostics for LogDiagnostics {
+    fn rejection(&self, rejection: &Rejection) {
+        log::info!("{rejection}");
+    }
+
+    fn note(&self, message: Arguments<'_>) {
+        log::warn!("{message}");
+    }
+}
+
+/// Diagnostics collected in memory, e.g. for tests.
+#[derive(Debug, Default)]
+pub struct VecDiagnostics {
+    rejections: Mutex<Vec<Rejection>>,
+    notes: Mutex<Vec<String>>,
+}
+
+impl VecDiagnostics {
+    /// Creates a new collector with no diagnostics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns rejections reported so far.
+    pub fn rejections(&self) -> Vec<Rejection> {
+        lock(&self.rejections).clone()
+    }
+
+    /// Returns notes reported so far.
+    pub fn notes(&self) -> Vec<String> {
+        lock(&self.notes).clone()
+    }
+}
+
+impl Diagnostics for VecDiagnostics {
+    fn rejection(&self, rejection: &Rejection) {
+        lock(&self.rejections).push(rejection.clone());
+    }
+
+    fn note(&self, message: Arguments<'_>) {
+        lock(&self.notes).push(message.to_string());
+    }
+}
+
+/// Locks the mutex ignoring poisoning as the data is always consistent.
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
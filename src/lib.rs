@@ -2,17 +2,50 @@
 #![deny(missing_debug_implementations)]
 #![deny(missing_docs)]
 
+mod amount;
+#[cfg(feature = "async")]
+mod asynchronous;
+mod diagnostics;
+mod parallel;
+mod snapshot;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
 use std::{
-    collections::{HashMap, HashSet},
-    path::Path,
+    collections::HashMap,
+    fs::File,
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use csv::{StringRecord, Trim};
+#[cfg(feature = "compression")]
+use flate2::read::GzDecoder;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{
+    de::Error as _,
+    ser::{Error as _, SerializeStruct},
+    Deserialize, Deserializer, Serialize,
 };
 
-use csv::Trim;
-use rust_decimal::Decimal;
-use serde::{ser::SerializeStruct, Deserialize, Serialize};
+#[cfg(feature = "async")]
+pub use crate::asynchronous::{process_async, process_async_with, process_stream};
+#[cfg(feature = "sqlite")]
+pub use crate::sqlite::process_sqlite;
+pub use crate::{
+    amount::Amount,
+    diagnostics::{Diagnostics, LogDiagnostics, VecDiagnostics},
+    parallel::{process_parallel, process_parallel_with},
+    snapshot::Snapshot,
+};
 
 /// Represents a type of a record.
-#[derive(Debug, Deserialize)]
+///
+/// Types are matched case-insensitively. Additionally `withdraw` is
+/// accepted as an alias of `withdrawal` and `charge-back` as well as
+/// `charge back` as aliases of `chargeback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum RecordType {
     /// Money deposit. Increases the available amount.
@@ -22,15 +55,94 @@ pub enum RecordType {
     Withdrawal,
 
     /// Transaction dispute. Moves funds from available to held.
+    ///
+    /// Transactions that have never been disputed as well as the ones
+    /// whose disputes have been resolved can be disputed. Transactions
+    /// that are already under dispute or have been charged back cannot.
     Dispute,
 
     /// Dispute resolution. Moves funds from held to available.
+    ///
+    /// Only transactions under dispute can be resolved. A resolved
+    /// transaction can be disputed again.
     Resolve,
 
     /// Chargeback. Freezes the account and decreases held funds.
+    ///
+    /// Only transactions under dispute can be charged back. The
+    /// chargeback is final: the transaction cannot be disputed,
+    /// resolved or charged back again.
+    ///
+    /// If the disputed transaction is a withdrawal the held funds are
+    /// returned to the available funds instead, see
+    /// [`WithdrawalDisputePolicy::Reverse`].
     Chargeback,
 }
 
+impl RecordType {
+    /// Canonical names of all types.
+    const NAMES: &'static [&'static str] =
+        &["deposit", "withdrawal", "dispute", "resolve", "chargeback"];
+
+    /// Parses the type as spelled in the input, including aliases,
+    /// returning `None` for unknown types.
+    fn from_name(value: &str) -> Option<Self> {
+        Some(match value.to_lowercase().as_str() {
+            "deposit" => RecordType::Deposit,
+            "withdrawal" | "withdraw" => RecordType::Withdrawal,
+            "dispute" => RecordType::Dispute,
+            "resolve" => RecordType::Resolve,
+            "chargeback" | "charge-back" | "charge back" => RecordType::Chargeback,
+            _ => return None,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for RecordType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::from_name(&value).ok_or_else(|| D::Error::unknown_variant(&value, Self::NAMES))
+    }
+}
+
+impl std::fmt::Display for RecordType {
+    /// Formats the type the same way it is spelled in the input.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RecordType::Deposit => "deposit",
+            RecordType::Withdrawal => "withdrawal",
+            RecordType::Dispute => "dispute",
+            RecordType::Resolve => "resolve",
+            RecordType::Chargeback => "chargeback",
+        })
+    }
+}
+
+impl std::str::FromStr for RecordType {
+    type Err = ParseRecordTypeError;
+
+    /// Parses the type the same way as it is parsed from the input,
+    /// i.e. case-insensitively and including aliases.
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        Self::from_name(value).ok_or_else(|| ParseRecordTypeError(value.into()))
+    }
+}
+
+/// Error returned when parsing an unknown [`RecordType`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Unknown record type {0:?}")]
+pub struct ParseRecordTypeError(String);
+
+impl ParseRecordTypeError {
+    /// Returns the value which could not be parsed.
+    pub fn value(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Number of decimal places of deposit and withdrawal amounts.
+const DECIMAL_PLACES: u32 = 4;
+
 /// Represents client identifier.
 pub type ClientId = u16;
 
@@ -38,7 +150,11 @@ pub type ClientId = u16;
 pub type TxId = u32;
 
 /// Transaction engine error.
+///
+/// New variants may be added in the future thus matching on the error
+/// needs a wildcard arm.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Deposit used but no amount has been specified.
     #[error("Deposit used but no amount is specified in transaction {0}")]
@@ -48,226 +164,4099 @@ pub enum Error {
     #[error("Withdraw used but no amount is specified in transaction {0}")]
     WithdrawNoAmount(TxId),
 
+    /// Deposit or withdrawal with a negative amount.
+    ///
+    /// Zero amounts are allowed. Returned by [`Record::validate`] and,
+    /// when processing, only in strict mode, see
+    /// [`ProcessOptions::strict`].
+    #[error("Transaction {tx} has a negative amount {amount}")]
+    NegativeAmount {
+        /// Identifier of the transaction.
+        tx: TxId,
+
+        /// The offending amount.
+        amount: Decimal,
+    },
+
+    /// Deposit or withdrawal amount has more than four decimal places.
+    ///
+    /// Returned only in strict mode (see [`ProcessOptions::strict`])
+    /// if [`ExcessPrecisionPolicy::Reject`] is used.
+    #[error("Transaction {0} has an amount with more than four decimal places")]
+    TooPrecise(TxId),
+
+    /// Dispute, resolve or chargeback has an amount.
+    ///
+    /// Only deposits and withdrawals carry amounts. Returned by
+    /// [`Record::validate`]. When processing, by default the
+    /// amount is logged and ignored, in strict mode (see
+    /// [`ProcessOptions::strict`]) this error is returned.
+    #[error("Transaction {tx} of type {kind} has an unexpected amount")]
+    UnexpectedAmount {
+        /// Identifier of the referenced transaction.
+        tx: TxId,
+
+        /// Type of the record.
+        kind: RecordType,
+    },
+
+    /// Deposit or withdrawal amount exceeds the configured limit.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`] and
+    /// [`ProcessOptions::max_transaction_amount`].
+    #[error("Transaction {tx} has an amount {amount} exceeding the limit of {limit}")]
+    AmountTooLarge {
+        /// Identifier of the transaction.
+        tx: TxId,
+
+        /// The offending amount.
+        amount: Decimal,
+
+        /// The configured limit.
+        limit: Decimal,
+    },
+
+    /// Withdrawal failed because there are not enough available funds.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {0} failed - insufficient funds")]
+    InsufficientFunds(TxId),
+
+    /// Resolve or chargeback failed because there are not enough held
+    /// funds.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {0} failed - insufficient held funds")]
+    InsufficientHeld(TxId),
+
+    /// Transaction failed because the resulting amount would overflow
+    /// (or the amount cannot be represented by the [`Amount`] type of
+    /// the engine).
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {0} failed - amount overflows")]
+    Overflow(TxId),
+
+    /// Referenced transaction does not exist.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {0} not found")]
+    TxNotFound(TxId),
+
+    /// Referenced transaction is a withdrawal which cannot be disputed.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {0} is a withdrawal and cannot be disputed")]
+    NotDisputable(TxId),
+
+    /// Referenced transaction is already under dispute.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {0} already under dispute")]
+    AlreadyDisputed(TxId),
+
+    /// Referenced transaction is not under dispute.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {0} not under dispute")]
+    NotDisputed(TxId),
+
+    /// Referenced transaction has already been charged back.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {0} has already been charged back")]
+    ChargedBack(TxId),
+
+    /// Transaction cannot be applied since the account is locked.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {tx} failed - account {client} is locked")]
+    AccountLocked {
+        /// Identifier of the locked account.
+        client: ClientId,
+
+        /// Identifier of the rejected transaction.
+        tx: TxId,
+    },
+
+    /// Transaction identifier has already been used.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {0} failed - duplicate transaction identifier")]
+    DuplicateTx(TxId),
+
+    /// Referenced transaction belongs to a different client.
+    ///
+    /// Returned only in strict mode, see [`ProcessOptions::strict`].
+    #[error("Transaction {tx} belongs to client {expected} but was referenced by client {got}")]
+    ClientMismatch {
+        /// Identifier of the referenced transaction.
+        tx: TxId,
+
+        /// Client owning the transaction.
+        expected: ClientId,
+
+        /// Client referencing the transaction.
+        got: ClientId,
+    },
+
+    /// Account is in an inconsistent state, see [`Account::validate`].
+    #[error("Account {client} violates invariant: {reason}")]
+    Invariant {
+        /// Identifier of the inconsistent account.
+        client: ClientId,
+
+        /// Description of the violated invariant.
+        reason: &'static str,
+    },
+
+    /// Record has a type which is not supported.
+    ///
+    /// Returned only in strict mode if
+    /// [`EngineConfig::skip_unknown_types`] is set. Otherwise unknown
+    /// types fail to parse with [`Error::Parse`].
+    #[error("Record {row} has an unknown type {value:?}")]
+    UnknownRecordType {
+        /// Number of the record, starting from 1 for the first record
+        /// after the header line.
+        row: u64,
+
+        /// The unknown type.
+        value: String,
+    },
+
+    /// Header line of the input does not contain all required columns.
+    #[error("Missing columns: {}", .0.join(", "))]
+    MissingColumns(Vec<String>),
+
+    /// Header line of the input contains unknown columns.
+    ///
+    /// Returned only if [`EngineConfig::reject_unknown_columns`] is set.
+    #[error("Unknown columns: {}", .0.join(", "))]
+    UnknownColumns(Vec<String>),
+
+    /// Value of the `client`, `tx` or `amount` column could not be
+    /// parsed.
+    ///
+    /// Returned instead of [`Error::Parse`] when the offending column is
+    /// known, e.g. for amounts which are not numbers or client
+    /// identifiers which are too big.
+    #[error("Invalid value {value:?} in column {column} of record {row}")]
+    InvalidField {
+        /// Number of the record, starting from 1 for the first record
+        /// after the header line.
+        row: u64,
+
+        /// Name of the column.
+        column: &'static str,
+
+        /// The raw value as found in the input (stripped of whitespace).
+        value: String,
+    },
+
+    /// Input record could not be parsed.
+    #[error("Parse error in record {row} (line {line}): {source}")]
+    Parse {
+        /// Line number (starting from 1) of the record that failed to
+        /// parse.
+        line: u64,
+
+        /// Number of the record, starting from 1 for the first record
+        /// after the header line. The header line itself is record 0.
+        row: u64,
+
+        /// The underlying CSV error.
+        source: csv::Error,
+    },
+
+    /// Record read from the input could not be applied.
+    ///
+    /// Wraps errors returned by [`Engine::apply`] when processing CSV
+    /// inputs so that the offending record can be found.
+    #[error("Record {row}: {source}")]
+    AtRow {
+        /// Number of the record, starting from 1 for the first record
+        /// after the header line.
+        row: u64,
+
+        /// Byte offset of the record in the input, if known.
+        byte: Option<u64>,
+
+        /// The error returned when applying the record.
+        source: Box<Error>,
+    },
+
+    /// Processing of a file failed.
+    ///
+    /// Wraps all errors, including the ones returned when opening the
+    /// file, of functions processing files so that the failing file
+    /// can be identified. Functions processing readers do not use it.
+    #[error("{}: {source}", path.display())]
+    InFile {
+        /// Path of the file.
+        path: PathBuf,
+
+        /// The error returned when processing the file.
+        source: Box<Error>,
+    },
+
     /// CSV serialization error.
     #[error("CSV serialization error: {0}")]
     Csv(#[from] csv::Error),
+
+    /// I/O error, for example when the input file cannot be opened.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Snapshot serialization error.
+    #[error("Snapshot serialization error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// Snapshot has been created by an incompatible version of the engine
+    /// or is not a snapshot at all.
+    #[error("Unsupported snapshot format")]
+    SnapshotVersion,
+
+    /// SQLite error.
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl Error {
+    /// Returns the number of the record which caused the error, if
+    /// known.
+    ///
+    /// Records are numbered from 1 for the first record after the
+    /// header line.
+    pub fn row(&self) -> Option<u64> {
+        match self {
+            Error::AtRow { row, .. }
+            | Error::Parse { row, .. }
+            | Error::InvalidField { row, .. }
+            | Error::UnknownRecordType { row, .. } => Some(*row),
+            Error::InFile { source, .. } => source.row(),
+            _ => None,
+        }
+    }
+
+    /// Returns the identifier of the transaction which caused the error,
+    /// if known.
+    pub fn tx_id(&self) -> Option<TxId> {
+        match self {
+            Error::DepositNoAmount(tx)
+            | Error::WithdrawNoAmount(tx)
+            | Error::NegativeAmount { tx, .. }
+            | Error::TooPrecise(tx)
+            | Error::UnexpectedAmount { tx, .. }
+            | Error::AmountTooLarge { tx, .. }
+            | Error::InsufficientFunds(tx)
+            | Error::InsufficientHeld(tx)
+            | Error::Overflow(tx)
+            | Error::TxNotFound(tx)
+            | Error::NotDisputable(tx)
+            | Error::AlreadyDisputed(tx)
+            | Error::NotDisputed(tx)
+            | Error::ChargedBack(tx)
+            | Error::AccountLocked { tx, .. }
+            | Error::DuplicateTx(tx)
+            | Error::ClientMismatch { tx, .. } => Some(*tx),
+            Error::AtRow { source, .. } | Error::InFile { source, .. } => source.tx_id(),
+            _ => None,
+        }
+    }
+
+    /// Returns the client whose record caused the error, if known.
+    ///
+    /// For [`Error::ClientMismatch`] this is the client of the record,
+    /// not the owner of the referenced transaction.
+    pub fn client(&self) -> Option<ClientId> {
+        match self {
+            Error::AccountLocked { client, .. }
+            | Error::ClientMismatch { got: client, .. }
+            | Error::Invariant { client, .. } => Some(*client),
+            Error::AtRow { source, .. } | Error::InFile { source, .. } => source.client(),
+            _ => None,
+        }
+    }
+
+    /// Returns a stable identifier of the kind of the error, e.g.
+    /// `deposit_no_amount`.
+    ///
+    /// Unlike the messages the identifiers never change thus they can
+    /// be used to handle errors programmatically. Errors wrapping other
+    /// errors ([`Error::AtRow`] and [`Error::InFile`]) return the
+    /// identifier of the wrapped error. Identifiers of errors which
+    /// are also rejections are equal to the serialized
+    /// [`RejectionReason`], e.g. `insufficient_funds`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::DepositNoAmount(_) => "deposit_no_amount",
+            Error::WithdrawNoAmount(_) => "withdraw_no_amount",
+            Error::NegativeAmount { .. } => "negative_amount",
+            Error::TooPrecise(_) => "too_precise",
+            Error::UnexpectedAmount { .. } => "unexpected_amount",
+            Error::AmountTooLarge { .. } => "amount_too_large",
+            Error::InsufficientFunds(_) => "insufficient_funds",
+            Error::InsufficientHeld(_) => "insufficient_held",
+            Error::Overflow(_) => "overflow",
+            Error::TxNotFound(_) => "tx_not_found",
+            Error::NotDisputable(_) => "not_disputable",
+            Error::AlreadyDisputed(_) => "already_disputed",
+            Error::NotDisputed(_) => "not_disputed",
+            Error::ChargedBack(_) => "charged_back",
+            Error::AccountLocked { .. } => "account_locked",
+            Error::DuplicateTx(_) => "duplicate_tx",
+            Error::ClientMismatch { .. } => "client_mismatch",
+            Error::Invariant { .. } => "invariant",
+            Error::UnknownRecordType { .. } => "unknown_record_type",
+            Error::MissingColumns(_) => "missing_columns",
+            Error::UnknownColumns(_) => "unknown_columns",
+            Error::InvalidField { .. } => "invalid_field",
+            Error::Parse { .. } => "parse",
+            Error::AtRow { source, .. } | Error::InFile { source, .. } => source.code(),
+            Error::Csv(_) => "csv",
+            Error::Io(_) => "io",
+            Error::Json(_) => "json",
+            Error::SnapshotVersion => "snapshot_version",
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(_) => "sqlite",
+        }
+    }
 }
 
 /// Result of transaction engine.
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Error which occurred while processing a single row of the input.
+///
+/// Returned by [`process_lenient`] which skips such rows instead of
+/// aborting the processing.
+#[derive(Debug)]
+pub struct RowError {
+    /// Number of the record (1-based, not counting the header line).
+    pub record: u64,
+
+    /// Contents of the offending row if it could be read.
+    ///
+    /// Since the values are stripped of whitespace this is a
+    /// reconstruction of the line and not necessarily its exact copy.
+    pub line: Option<String>,
+
+    /// The underlying error.
+    pub error: Error,
+}
+
+/// Reason why a record has been rejected.
+///
+/// Each reason corresponds to an [`Error`] variant returned in strict
+/// mode.
+///
+/// Serialized as snake case names of the variants, e.g.
+/// `insufficient_funds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum RejectionReason {
+    /// Available funds are lower than the amount, see
+    /// [`Error::InsufficientFunds`].
+    InsufficientFunds,
+
+    /// Held funds are lower than the amount, see
+    /// [`Error::InsufficientHeld`].
+    InsufficientHeld,
+
+    /// Amounts of the account would overflow, see [`Error::Overflow`].
+    Overflow,
+
+    /// Referenced transaction does not exist, see [`Error::TxNotFound`].
+    TxNotFound,
+
+    /// Referenced transaction cannot be disputed, see
+    /// [`Error::NotDisputable`].
+    NotDisputable,
+
+    /// Referenced transaction is already under dispute, see
+    /// [`Error::AlreadyDisputed`].
+    AlreadyDisputed,
+
+    /// Referenced transaction is not under dispute, see
+    /// [`Error::NotDisputed`].
+    NotDisputed,
+
+    /// Referenced transaction has been charged back, see
+    /// [`Error::ChargedBack`].
+    ChargedBack,
+
+    /// Referenced transaction belongs to a different client, see
+    /// [`Error::ClientMismatch`].
+    ClientMismatch,
+
+    /// Account is locked, see [`Error::AccountLocked`].
+    AccountLocked,
+
+    /// Transaction identifier has already been used, see
+    /// [`Error::DuplicateTx`].
+    DuplicateTx,
+
+    /// Amount is negative, see [`Error::NegativeAmount`].
+    NegativeAmount,
+
+    /// Amount has too many decimal places, see [`Error::TooPrecise`].
+    TooPrecise,
+
+    /// Amount exceeds the configured limit, see
+    /// [`Error::AmountTooLarge`].
+    AmountTooLarge,
+}
+
+impl RejectionReason {
+    /// Returns a stable identifier of the reason, e.g.
+    /// `insufficient_funds`.
+    ///
+    /// The identifier is equal to the serialized reason and to
+    /// [`Error::code`] of the corresponding error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RejectionReason::InsufficientFunds => "insufficient_funds",
+            RejectionReason::InsufficientHeld => "insufficient_held",
+            RejectionReason::Overflow => "overflow",
+            RejectionReason::TxNotFound => "tx_not_found",
+            RejectionReason::NotDisputable => "not_disputable",
+            RejectionReason::AlreadyDisputed => "already_disputed",
+            RejectionReason::NotDisputed => "not_disputed",
+            RejectionReason::ChargedBack => "charged_back",
+            RejectionReason::ClientMismatch => "client_mismatch",
+            RejectionReason::AccountLocked => "account_locked",
+            RejectionReason::DuplicateTx => "duplicate_tx",
+            RejectionReason::NegativeAmount => "negative_amount",
+            RejectionReason::TooPrecise => "too_precise",
+            RejectionReason::AmountTooLarge => "amount_too_large",
+        }
+    }
+
+    /// Returns the reason of the rejection if the error is returned only
+    /// in strict mode.
+    fn of(error: &Error) -> Option<Self> {
+        Some(match error {
+            Error::InsufficientFunds(_) => RejectionReason::InsufficientFunds,
+            Error::InsufficientHeld(_) => RejectionReason::InsufficientHeld,
+            Error::Overflow(_) => RejectionReason::Overflow,
+            Error::TxNotFound(_) => RejectionReason::TxNotFound,
+            Error::NotDisputable(_) => RejectionReason::NotDisputable,
+            Error::AlreadyDisputed(_) => RejectionReason::AlreadyDisputed,
+            Error::NotDisputed(_) => RejectionReason::NotDisputed,
+            Error::ChargedBack(_) => RejectionReason::ChargedBack,
+            Error::ClientMismatch { .. } => RejectionReason::ClientMismatch,
+            Error::AccountLocked { .. } => RejectionReason::AccountLocked,
+            Error::DuplicateTx(_) => RejectionReason::DuplicateTx,
+            Error::NegativeAmount { .. } => RejectionReason::NegativeAmount,
+            Error::TooPrecise(_) => RejectionReason::TooPrecise,
+            Error::AmountTooLarge { .. } => RejectionReason::AmountTooLarge,
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RejectionReason::InsufficientFunds => "insufficient funds",
+            RejectionReason::InsufficientHeld => "insufficient held funds",
+            RejectionReason::Overflow => "amount overflow",
+            RejectionReason::TxNotFound => "transaction not found",
+            RejectionReason::NotDisputable => "transaction cannot be disputed",
+            RejectionReason::AlreadyDisputed => "transaction already under dispute",
+            RejectionReason::NotDisputed => "transaction not under dispute",
+            RejectionReason::ChargedBack => "transaction charged back",
+            RejectionReason::ClientMismatch => "transaction of a different client",
+            RejectionReason::AccountLocked => "account locked",
+            RejectionReason::DuplicateTx => "duplicate transaction",
+            RejectionReason::NegativeAmount => "negative amount",
+            RejectionReason::TooPrecise => "too many decimal places",
+            RejectionReason::AmountTooLarge => "amount exceeding the limit",
+        })
+    }
+}
+
+/// Notable occurrence during processing, e.g. a rejected record.
+///
+/// All events are logged and can be additionally received by a sink set
+/// with [`Engine::set_event_sink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// Record has been rejected.
+    Rejected(Rejection),
+
+    /// Dispute, resolve or chargeback has an amount which is ignored.
+    ///
+    /// See [`Error::UnexpectedAmount`] for the error returned in strict
+    /// mode.
+    UnexpectedAmount {
+        /// Number of the record, see [`Rejection::row`].
+        row: u64,
+
+        /// Identifier of the referenced transaction.
+        tx: TxId,
+
+        /// Type of the record.
+        kind: RecordType,
+    },
+
+    /// Record with an unknown type has been skipped.
+    ///
+    /// Emitted only if [`EngineConfig::skip_unknown_types`] is set. See
+    /// [`Error::UnknownRecordType`] for the error returned in strict
+    /// mode.
+    UnknownRecordType {
+        /// Number of the record, see [`Rejection::row`].
+        row: u64,
+
+        /// The unknown type.
+        value: String,
+    },
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::Rejected(rejection) => write!(f, "{rejection}"),
+            Event::UnexpectedAmount { row, tx, kind } => write!(
+                f,
+                "Record {row} (transaction {tx} of type {kind}) has an unexpected amount"
+            ),
+            Event::UnknownRecordType { row, value } => {
+                write!(f, "Record {row} has an unknown type {value:?}")
+            }
+        }
+    }
+}
+
+/// Record which has been logged and ignored instead of being applied.
+///
+/// Returned by [`process_with_report`] (as part of [`ProcessReport`])
+/// and [`Engine::rejections`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rejection {
+    /// Number of the record (1-based, not counting the header line).
+    ///
+    /// Zero for records passed directly to [`Engine::apply`].
+    pub row: u64,
+
+    /// Transaction identifier of the record.
+    pub tx: TxId,
+
+    /// Client of the record.
+    pub client: ClientId,
+
+    /// Type of the record.
+    #[serde(rename = "type")]
+    pub kind: RecordType,
+
+    /// Amount of the record, if any.
+    pub amount: Option<Decimal>,
+
+    /// Why the record has been rejected.
+    pub reason: RejectionReason,
+}
+
+/// Row of the input which has been skipped since it could not be
+/// parsed as a record.
+///
+/// Only rows with unknown record types are skipped, see
+/// [`EngineConfig::skip_unknown_types`]. Returned by
+/// [`process_with_report`] (as part of [`ProcessReport`]) and
+/// [`Engine::skipped`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedRow {
+    /// Number of the record, see [`Rejection::row`].
+    pub row: u64,
+
+    /// Contents of the row.
+    ///
+    /// Since the values are stripped of whitespace this is a
+    /// reconstruction of the line and not necessarily its exact copy.
+    pub raw: String,
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Record {} (transaction {} of client {}) rejected: {}",
+            self.row, self.tx, self.client, self.reason
+        )
+    }
+}
+
 /// Single record.
 #[derive(Debug, Deserialize)]
 pub struct Record {
     /// Type of the record.
-    #[serde(rename = "type")]
+    #[serde(rename = "type", alias = "transaction_type")]
     pub kind: RecordType,
 
     /// Identifies client account.
+    #[serde(alias = "client_id")]
     pub client: ClientId,
 
     /// Specifies transaction identifier. For example deposits and
     /// withdrawals can be referenced by disputes.
+    #[serde(alias = "tx_id")]
     pub tx: TxId,
 
     /// The amount that this transaction represents. Note that only
     /// deposits and withdrawals will contain the amount. Other record
     /// types use the amount from referenced transactions.
+    #[serde(default, deserialize_with = "deserialize_amount")]
     pub amount: Option<Decimal>,
 }
 
+impl Record {
+    /// Checks if the record is well-formed without applying it.
+    ///
+    /// Deposits and withdrawals must have an amount which is not
+    /// negative while other record types must not have an amount.
+    /// Checks which depend on the state of the engine or its options
+    /// (e.g. insufficient funds or the precision of the amount) are not
+    /// performed.
+    ///
+    /// [`Engine::apply`] validates every record before applying it.
+    pub fn validate(&self) -> Result<()> {
+        match (self.kind, self.amount) {
+            (RecordType::Deposit, None) => Err(Error::DepositNoAmount(self.tx)),
+            (RecordType::Withdrawal, None) => Err(Error::WithdrawNoAmount(self.tx)),
+            (RecordType::Deposit | RecordType::Withdrawal, Some(amount))
+                if amount < Decimal::ZERO =>
+            {
+                Err(Error::NegativeAmount {
+                    tx: self.tx,
+                    amount,
+                })
+            }
+            (RecordType::Deposit | RecordType::Withdrawal, Some(_)) => Ok(()),
+            (_, Some(_)) => Err(Error::UnexpectedAmount {
+                tx: self.tx,
+                kind: self.kind,
+            }),
+            (_, None) => Ok(()),
+        }
+    }
+}
+
+/// Deserializes the amount from its textual representation.
+///
+/// Parsing the text directly, instead of letting the CSV reader infer a
+/// floating point number, preserves all digits of the amount, including
+/// the ones of values close to [`Decimal::MAX`].
+fn deserialize_amount<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Decimal>, D::Error> {
+    let Some(amount) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    parse_amount(&amount).map(Some).map_err(D::Error::custom)
+}
+
+/// Parses the amount accepting both the plain and the scientific
+/// notation.
+fn parse_amount(amount: &str) -> std::result::Result<Decimal, rust_decimal::Error> {
+    let amount = amount
+        .parse::<Decimal>()
+        .or_else(|_| Decimal::from_scientific(amount))?;
+    // trailing zeros are not significant
+    Ok(amount.normalize())
+}
+
+/// Numbers of records processed by the engine.
+///
+/// Unlike [`Summary`] the counts are not split by clients. They are a
+/// cheap health signal, e.g. a growing share of rejected records may
+/// indicate a problem with the upstream system.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EngineStats {
+    /// Number of records which have been applied.
+    pub applied: u64,
+
+    /// Number of records which have been rejected, see
+    /// [`Engine::rejections`].
+    pub rejected: u64,
+
+    /// Numbers of rejected records by the reason of the rejection.
+    pub rejected_by_reason: HashMap<RejectionReason, u64>,
+
+    /// Numbers of records passed to the engine by their types,
+    /// including the ones which have failed.
+    pub records_by_type: HashMap<RecordType, u64>,
+
+    /// Number of records of unknown types which have been skipped, see
+    /// [`EngineConfig::skip_unknown_types`].
+    pub unknown_types: u64,
+
+    /// Number of disputes, resolves and chargebacks whose amounts have
+    /// been ignored.
+    pub unexpected_amounts: u64,
+}
+
+/// Summary of processing an input, see [`process_with_report`].
+///
+/// Serialized without the list of rejections which can be very long.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct ProcessReport {
+    /// Number of records read from the input, including skipped records
+    /// of unknown types.
+    pub records: u64,
+
+    /// Numbers of records by their types.
+    pub records_by_type: HashMap<RecordType, u64>,
+
+    /// Numbers of rejected records by the reason of the rejection.
+    pub rejected_by_reason: HashMap<RejectionReason, u64>,
+
+    /// Rejected records in the order of the input.
+    #[serde(skip)]
+    pub rejections: Vec<Rejection>,
+
+    /// Skipped rows in the order of the input.
+    #[serde(skip)]
+    pub skipped: Vec<SkippedRow>,
+
+    /// Number of accounts.
+    pub accounts: usize,
+
+    /// Number of locked accounts.
+    pub locked_accounts: usize,
+
+    /// Sum of total funds of all accounts, `None` if it overflows.
+    ///
+    /// Without chargebacks this is equal to the sum of the applied
+    /// deposits minus the sum of the applied withdrawals which can be
+    /// used to check the results against the input.
+    pub total: Option<Decimal>,
+}
+
+/// Number of transactions applied to an account.
+///
+/// Only records which changed the state of the engine are counted,
+/// rejected records (e.g. withdrawals with insufficient funds) are not.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Summary {
+    /// Number of deposits.
+    pub deposits: u64,
+
+    /// Number of withdrawals.
+    pub withdrawals: u64,
+
+    /// Number of disputes.
+    pub disputes: u64,
+
+    /// Number of resolved disputes.
+    pub resolves: u64,
+
+    /// Number of chargebacks.
+    pub chargebacks: u64,
+}
+
 /// Represents client account.
 ///
 /// The account has associated funds stored in the `amounts` field and
-/// can be frozen (`locked`).
-#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
-pub struct Account {
+/// can be frozen (`locked`). The funds are [`Decimal`] unless the
+/// engine uses a different [`Amount`] type.
+///
+/// The dispute counters and the locking chargeback are not a part of
+/// the default output, see [`write_accounts_extended`].
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Account<A = Decimal> {
     /// Identifier of this account.
     pub client: ClientId,
 
     /// Funds associated with this account.
     #[serde(flatten)]
-    pub amounts: Amounts,
+    pub amounts: AmountsOf<A>,
 
     /// Frozen status of this account. The account is only frozen if a
-    /// successful chargeback occurs.
+    /// successful chargeback occurs. Frozen accounts do not accept
+    /// deposits and withdrawals.
     pub locked: bool,
+
+    /// Number of disputes of transactions of this account.
+    #[serde(default)]
+    pub disputes_opened: u64,
+
+    /// Number of resolved disputes of transactions of this account.
+    #[serde(default)]
+    pub disputes_resolved: u64,
+
+    /// Number of chargebacks of transactions of this account.
+    #[serde(default)]
+    pub chargebacks: u64,
+
+    /// Chargeback which has frozen this account, if any.
+    #[serde(default)]
+    pub locked_by: Option<TxId>,
 }
 
-impl Serialize for Account {
-    /// Serializes account. The inner amounts (available and held) are serialized as
-    /// usual. Total is added as a computed field.
-    /// Sadly, #[serde(flatten)] is not supported by the "rust-csv" create, see:
-    /// <https://github.com/BurntSushi/rust-csv/pull/223>
-    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let mut x = serializer.serialize_struct("Account", 3)?;
-        x.serialize_field("client", &self.client)?;
-        x.serialize_field("available", &self.amounts.available)?;
-        x.serialize_field("held", &self.amounts.held)?;
-        // total is always the sum of available and held
-        x.serialize_field("total", &self.amounts.total())?;
-        x.serialize_field("locked", &self.locked)?;
-        x.end()
+/// Accounts are equal if they have the same client, funds and frozen
+/// status.
+///
+/// The dispute counters and the locking chargeback describe the history
+/// of the account and are not compared, thus accounts read from the
+/// default output are equal to the ones computed by the engine.
+impl<A: PartialEq> PartialEq for Account<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.client == other.client && self.amounts == other.amounts && self.locked == other.locked
     }
 }
 
-/// Funds associated with the account.
-///
-/// The funds are split into two buckets:
-///    - available - funds that the client can use in their transactions,
-///    - held - funds that are held because of pending disputes.
-///
-/// Additionally there's a total getter which is a sum of the previous two.
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
-pub struct Amounts {
-    /// Funds that the client can use in transactions.
-    pub available: Decimal,
+impl<A: Eq> Eq for Account<A> {}
 
-    /// Funds that are blocked because of pending disputes.
-    pub held: Decimal,
-}
+impl<A: Amount> Account<A> {
+    /// Returns `true` if this account is frozen.
+    ///
+    /// Locked accounts reject deposits (unless
+    /// [`ProcessOptions::locked_deposits`] is set) and withdrawals.
+    /// Disputes, resolves and chargebacks of transactions that were
+    /// applied before the account got locked are still processed so
+    /// that pending disputes can be settled.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
 
-impl Amounts {
-    /// Deposits new funds which increases the available amount.
-    pub fn deposit(&mut self, amount: Decimal) {
-        self.available += amount;
+    /// Checks that the funds of this account are not negative, see
+    /// [`AmountsOf::is_solvent`].
+    pub fn is_solvent(&self) -> bool {
+        self.amounts.is_solvent()
     }
 
-    /// Withdraws funds which decreases the available amount.
+    /// Returns the total funds of this account, see
+    /// [`AmountsOf::total`].
     ///
-    /// Note that if the withdrawing amount is bigger than the
-    /// available funds the operation is a no-op.  This function
-    /// returns `true` on success and `false` on failure.
-    pub fn withdraw(&mut self, amount: Decimal) -> bool {
-        if self.available >= amount {
-            self.available -= amount;
-            true
-        } else {
-            false
-        }
+    /// Panics if the total overflows which is not possible for accounts
+    /// passing [`Account::validate`].
+    pub fn total(&self) -> A {
+        self.amounts.total()
     }
 
-    /// Marks a certain amount of funds as held for dispute.
+    /// Checks that the funds of this account are consistent.
     ///
-    /// Decreases the available amount and increases the held amount
-    /// by the same value.
-    pub fn hold(&mut self, amount: Decimal) {
-        self.available -= amount;
-        self.held += amount;
+    /// The held funds must not be negative and the total must be
+    /// representable as a sum of available and held funds. Returns
+    /// [`Error::Invariant`] otherwise.
+    pub fn validate(&self) -> Result<()> {
+        let reason = if self.amounts.held < A::ZERO {
+            "held funds are negative"
+        } else if self.amounts.checked_total().is_none() {
+            "total amount overflows"
+        } else {
+            return Ok(());
+        };
+        Err(Error::Invariant {
+            client: self.client,
+            reason,
+        })
     }
+}
 
-    /// Releases funds previously held for dispute.
+impl Account {
+    /// Compares accounts by their total funds.
+    ///
+    /// Accounts with equal totals are ordered by their clients so that
+    /// sorting is deterministic. Accounts whose totals overflow are
+    /// ordered before all other accounts. Use
+    /// `accounts.sort_by(|a, b| b.cmp_by_total(a))` to rank clients by
+    /// their funds, the richest first.
+    pub fn cmp_by_total(&self, other: &Account) -> std::cmp::Ordering {
+        self.amounts
+            .checked_total()
+            .cmp(&other.amounts.checked_total())
+            .then(self.client.cmp(&other.client))
+    }
+
+    /// Returns a view of this account which serializes amounts rounded
+    /// using given precision.
+    ///
+    /// The account itself retains full precision of amounts.
+    pub fn rounded(&self, precision: Precision) -> RoundedAccount<'_> {
+        RoundedAccount {
+            account: self,
+            precision,
+        }
+    }
+}
+
+impl Serialize for Account {
+    /// Serializes account with amounts rounded using the default
+    /// [`Precision`].
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.rounded(Precision::default()).serialize(serializer)
+    }
+}
+
+/// Precision of serialized amounts.
+///
+/// By default the amounts are rounded to four decimal places using
+/// banker's rounding (half to even). The amounts are always serialized
+/// with exactly the given number of decimal places, e.g. `1.5000`, so
+/// that equal amounts are equal strings.
+#[derive(Debug, Clone, Copy)]
+pub struct Precision {
+    /// Number of decimal places.
+    ///
+    /// Amounts of accounts have at most four decimal places, values
+    /// above four only add trailing zeros.
+    pub decimal_places: u32,
+
+    /// Strategy used to round the amounts.
+    pub strategy: RoundingStrategy,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Self {
+            decimal_places: 4,
+            strategy: RoundingStrategy::MidpointNearestEven,
+        }
+    }
+}
+
+impl Precision {
+    /// Rounds the amount and pads it with zeros to the number of decimal
+    /// places.
+    fn round(&self, amount: Decimal) -> Decimal {
+        let mut rounded = amount.round_dp_with_strategy(self.decimal_places, self.strategy);
+        rounded.rescale(self.decimal_places);
+        // amounts rounded to zero, e.g. `-0.00001`, are printed without
+        // the sign
+        if rounded.is_zero() {
+            rounded.set_sign_positive(true);
+        }
+        rounded
+    }
+}
+
+/// Format of the CSV output, see [`write_accounts_with`].
+#[derive(Debug, Clone, Copy)]
+pub struct OutputOptions {
+    /// Precision of the amounts.
+    ///
+    /// This is not a part of [`EngineConfig`] since the precision only
+    /// matters when the accounts are written, the same state can be
+    /// written with different precisions. The stored amounts have at
+    /// most four decimal places thus more places only pad the amounts
+    /// with zeros.
+    pub precision: Precision,
+
+    /// Character separating the fields, e.g. `b'\t'`.
+    ///
+    /// Fields containing the delimiter are quoted.
+    pub delimiter: u8,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            precision: Precision::default(),
+            delimiter: b',',
+        }
+    }
+}
+
+impl OutputOptions {
+    /// Creates a CSV writer using these options.
+    fn writer<W: Write>(&self, writer: W) -> csv::Writer<W> {
+        csv::WriterBuilder::new()
+            .has_headers(false)
+            .delimiter(self.delimiter)
+            .from_writer(writer)
+    }
+}
+
+/// Account which serializes amounts with specific precision.
+///
+/// Created by [`Account::rounded`].
+#[derive(Debug)]
+pub struct RoundedAccount<'a> {
+    account: &'a Account,
+    precision: Precision,
+}
+
+impl Serialize for RoundedAccount<'_> {
+    /// Serializes account. The inner amounts (available and held) are serialized as
+    /// usual. Total is added as a computed field. If the total cannot be
+    /// computed because of an overflow an error is returned.
+    /// Sadly, #[serde(flatten)] is not supported by the "rust-csv" create, see:
+    /// <https://github.com/BurntSushi/rust-csv/pull/223>
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let account = self.account;
+        let precision = &self.precision;
+        let mut x = serializer.serialize_struct("Account", 5)?;
+        x.serialize_field("client", &account.client)?;
+        x.serialize_field("available", &precision.round(account.amounts.available))?;
+        x.serialize_field("held", &precision.round(account.amounts.held))?;
+        // total is always the sum of available and held
+        let total = account
+            .amounts
+            .checked_total()
+            .ok_or_else(|| S::Error::custom("total amount overflows"))?;
+        x.serialize_field("total", &precision.round(total))?;
+        x.serialize_field("locked", &account.locked)?;
+        x.end()
+    }
+}
+
+/// Funds associated with the account.
+///
+/// The funds are split into two buckets:
+///    - available - funds that the client can use in their transactions,
+///    - held - funds that are held because of pending disputes.
+///
+/// Additionally there's a total getter which is a sum of the previous two.
+///
+/// The engine uses [`Amounts`] which hold [`Decimal`] values. Other
+/// numeric types can be used by implementing [`Amount`] for them.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct AmountsOf<A> {
+    /// Funds that the client can use in transactions.
+    pub available: A,
+
+    /// Funds that are blocked because of pending disputes.
+    pub held: A,
+}
+
+/// Funds of accounts used by the engine.
+pub type Amounts = AmountsOf<Decimal>;
+
+impl<A: Amount> AmountsOf<A> {
+    /// Returns funds that the client can use in transactions.
+    pub fn available(&self) -> A {
+        self.available
+    }
+
+    /// Returns funds that are blocked because of pending disputes.
+    pub fn held(&self) -> A {
+        self.held
+    }
+
+    /// Checks that neither available nor held funds are negative.
+    ///
+    /// Available funds may become negative only if disputes are allowed
+    /// to overdraw the account, see
+    /// [`DisputeOverdraftPolicy::AllowNegative`].
+    pub fn is_solvent(&self) -> bool {
+        self.available >= A::ZERO && self.held >= A::ZERO
+    }
+
+    /// Deposits new funds which increases the available amount.
+    ///
+    /// Fails with [`AmountsError::Overflow`] if the available amount
+    /// cannot be represented.
+    pub fn deposit(&mut self, amount: A) -> std::result::Result<(), AmountsError> {
+        self.available = checked_add(self.available, amount)?;
+        Ok(())
+    }
+
+    /// Withdraws funds which decreases the available amount.
+    ///
+    /// Fails with [`AmountsError::InsufficientFunds`] if the withdrawing
+    /// amount is bigger than the available funds.
+    pub fn withdraw(&mut self, amount: A) -> std::result::Result<(), AmountsError> {
+        if self.available < amount {
+            return Err(AmountsError::InsufficientFunds);
+        }
+        self.available = checked_sub(self.available, amount)?;
+        Ok(())
+    }
+
+    /// Marks a certain amount of funds as held for dispute.
+    ///
+    /// Decreases the available amount and increases the held amount
+    /// by the same value. Note that this may make the available amount
+    /// negative, see [`AmountsOf::try_hold`] for a checked variant.
+    ///
+    /// Returns the amount that has been held which is always equal to
+    /// `amount`.
+    pub fn hold(&mut self, amount: A) -> std::result::Result<A, AmountsError> {
+        let available = checked_sub(self.available, amount)?;
+        self.held = checked_add(self.held, amount)?;
+        self.available = available;
+        Ok(amount)
+    }
+
+    /// Marks at most the available amount of funds as held for dispute.
+    ///
+    /// The available amount never goes below zero. Returns the amount
+    /// that has actually been held which needs to be used when the
+    /// dispute is resolved or charged back.
+    pub fn hold_clamped(&mut self, amount: A) -> std::result::Result<A, AmountsError> {
+        let limit = if self.available > A::ZERO {
+            self.available
+        } else {
+            A::ZERO
+        };
+        self.hold(if amount < limit { amount } else { limit })
+    }
+
+    /// Marks a certain amount of funds as held for dispute only if
+    /// there are enough available funds.
+    ///
+    /// Fails with [`AmountsError::InsufficientFunds`] if the held
+    /// amount is bigger than the available funds.
+    pub fn try_hold(&mut self, amount: A) -> std::result::Result<(), AmountsError> {
+        if self.available < amount {
+            return Err(AmountsError::InsufficientFunds);
+        }
+        self.hold(amount)?;
+        Ok(())
+    }
+
+    /// Releases funds previously held for dispute.
     ///
     /// Decreases the held amount and increases the available amount.
-    pub fn release(&mut self, amount: Decimal) {
-        self.held -= amount;
-        self.available += amount;
+    /// Fails with [`AmountsError::InsufficientHeld`] if the amount is
+    /// bigger than the held funds.
+    pub fn release(&mut self, amount: A) -> std::result::Result<(), AmountsError> {
+        if self.held < amount {
+            return Err(AmountsError::InsufficientHeld);
+        }
+        let held = checked_sub(self.held, amount)?;
+        self.available = checked_add(self.available, amount)?;
+        self.held = held;
+        Ok(())
+    }
+
+    /// Completes the chargeback procedure removing held funds from this account.
+    ///
+    /// Fails with [`AmountsError::InsufficientHeld`] if the amount is
+    /// bigger than the held funds.
+    pub fn chargeback(&mut self, amount: A) -> std::result::Result<(), AmountsError> {
+        if self.held < amount {
+            return Err(AmountsError::InsufficientHeld);
+        }
+        self.held = checked_sub(self.held, amount)?;
+        Ok(())
+    }
+
+    /// Marks funds of a disputed withdrawal as held.
+    ///
+    /// Increases only the held amount since the withdrawn funds are no
+    /// longer available. If the dispute is charged back the funds are
+    /// returned to the client with [`AmountsOf::release`], otherwise they
+    /// are removed with [`AmountsOf::chargeback`].
+    pub fn hold_reversal(&mut self, amount: A) -> std::result::Result<(), AmountsError> {
+        self.held = checked_add(self.held, amount)?;
+        Ok(())
+    }
+
+    /// Returns a total amount which is a sum of held and available funds.
+    ///
+    /// Panics if the sum overflows, see [`AmountsOf::checked_total`].
+    pub fn total(&self) -> A {
+        self.checked_total().expect("total funds to not overflow")
+    }
+
+    /// Returns a total amount which is a sum of held and available funds
+    /// or `None` if the sum overflows.
+    pub fn checked_total(&self) -> Option<A> {
+        self.available.checked_add(self.held)
+    }
+}
+
+/// Error returned when funds of an account cannot be changed.
+///
+/// If an operation fails the funds are left unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum AmountsError {
+    /// There are not enough available funds.
+    #[error("insufficient funds")]
+    InsufficientFunds,
+
+    /// There are not enough held funds.
+    #[error("insufficient held funds")]
+    InsufficientHeld,
+
+    /// The resulting amount cannot be represented, e.g. does not fit in
+    /// [`Decimal`].
+    #[error("amount overflows")]
+    Overflow,
+}
+
+impl AmountsError {
+    /// Converts this error to the engine error of given transaction.
+    fn into_error(self, tx: TxId) -> Error {
+        match self {
+            AmountsError::InsufficientFunds => Error::InsufficientFunds(tx),
+            AmountsError::InsufficientHeld => Error::InsufficientHeld(tx),
+            AmountsError::Overflow => Error::Overflow(tx),
+        }
+    }
+}
+
+/// Adds two amounts failing with [`AmountsError::Overflow`] instead of
+/// panicking.
+fn checked_add<A: Amount>(left: A, right: A) -> std::result::Result<A, AmountsError> {
+    left.checked_add(right).ok_or(AmountsError::Overflow)
+}
+
+/// Subtracts two amounts failing with [`AmountsError::Overflow`]
+/// instead of panicking.
+fn checked_sub<A: Amount>(left: A, right: A) -> std::result::Result<A, AmountsError> {
+    left.checked_sub(right).ok_or(AmountsError::Overflow)
+}
+
+/// Configuration of the engine.
+///
+/// All fields are public so that the configuration can be created with
+/// the struct syntax. The default configuration matches the behavior of
+/// [`process`]:
+///
+/// ```
+/// use tx_engine::{Engine, EngineConfig};
+///
+/// let engine = Engine::with_config(EngineConfig {
+///     strict: true,
+///     ..Default::default()
+/// });
+/// ```
+///
+/// See [`ProcessOptions`] for a builder-style alternative.
+///
+/// The configuration affects only how records are applied. Amounts of
+/// deposits and withdrawals are adjusted to four decimal places when
+/// they are applied (see [`EngineConfig::excess_precision`]) and the
+/// engine does not round them any further, thus the precision of the
+/// output is chosen when writing the accounts, see [`OutputOptions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Returns errors for operations which cannot be performed.
+    ///
+    /// If disabled, operations which cannot be performed (e.g.
+    /// withdrawals with insufficient funds) are logged and ignored.
+    ///
+    /// Default: `false`.
+    pub strict: bool,
+
+    /// Allows deposits to locked accounts.
+    ///
+    /// Locked accounts never accept withdrawals. If disabled they do not
+    /// accept deposits either.
+    ///
+    /// Default: `false`.
+    pub locked_deposits: bool,
+
+    /// Rejects deposits and withdrawals reusing an existing transaction
+    /// identifier.
+    ///
+    /// Such transactions are logged and ignored (or return
+    /// [`Error::DuplicateTx`] in strict mode). If disabled, such
+    /// transactions are applied to the account but cannot be referenced
+    /// by disputes, since disputes always reference the first
+    /// transaction with a given identifier.
+    ///
+    /// Default: `true`.
+    pub reject_duplicate_tx: bool,
+
+    /// Specifies how disputes of withdrawals are handled.
+    ///
+    /// Default: [`WithdrawalDisputePolicy::Ignore`].
+    pub withdrawal_disputes: WithdrawalDisputePolicy,
+
+    /// Specifies how disputes exceeding available funds are handled.
+    ///
+    /// Default: [`DisputeOverdraftPolicy::Reject`].
+    pub dispute_overdraft: DisputeOverdraftPolicy,
+
+    /// Specifies how amounts with more than four decimal places are
+    /// handled.
+    ///
+    /// Default: [`ExcessPrecisionPolicy::RoundHalfEven`].
+    pub excess_precision: ExcessPrecisionPolicy,
+
+    /// Maximum amount of a single deposit or withdrawal.
+    ///
+    /// Bigger transactions are logged and ignored (or return
+    /// [`Error::AmountTooLarge`] in strict mode). Since they are not
+    /// stored they cannot be referenced by disputes.
+    ///
+    /// Default: `None` (unlimited).
+    pub max_transaction_amount: Option<Decimal>,
+
+    /// Rejects inputs with columns other than `type`, `client`, `tx`
+    /// and `amount` with [`Error::UnknownColumns`].
+    ///
+    /// Default: `false`.
+    pub reject_unknown_columns: bool,
+
+    /// Skips records with unknown types (e.g. `fee`).
+    ///
+    /// Such records are logged and ignored (or return
+    /// [`Error::UnknownRecordType`] in strict mode). If disabled they
+    /// fail to parse.
+    ///
+    /// Affects the same functions as [`EngineConfig::two_pass`].
+    ///
+    /// Default: `false`.
+    pub skip_unknown_types: bool,
+
+    /// Applies disputes, resolves and chargebacks only after all
+    /// deposits and withdrawals of the input.
+    ///
+    /// Allows disputes to reference transactions which appear later in
+    /// the same input, e.g. in feeds which do not preserve the order of
+    /// records. Disputes, resolves and chargebacks are kept in memory
+    /// until the whole input has been read, thus the memory usage grows
+    /// with their number instead of being constant. Since deposits and
+    /// withdrawals are applied first, chargebacks do not block
+    /// deposits and withdrawals of the same input and disputes do not
+    /// block withdrawals of disputed funds.
+    ///
+    /// Affects only [`Engine::process_reader`] and functions built on
+    /// it, e.g. [`Engine::process_file`] and [`process_with`]. Each
+    /// input is processed separately so disputes cannot reference
+    /// transactions of subsequent files.
+    ///
+    /// Default: `false`.
+    pub two_pass: bool,
+
+    /// Character separating the fields of the input, e.g. `b'\t'`.
+    ///
+    /// Affects all functions reading CSV input with given options, e.g.
+    /// [`Engine::process_reader`], [`process_with`],
+    /// [`process_reader_lenient_with`], [`process_client_with`] and
+    /// [`process_parallel_with`].
+    ///
+    /// Default: `b','`.
+    pub delimiter: u8,
+
+    /// Expects the first line of the input to name the columns.
+    ///
+    /// If disabled every line is a record with the `type`, `client`,
+    /// `tx` and `amount` columns, in this order, and the columns are
+    /// not checked (see [`EngineConfig::reject_unknown_columns`]).
+    /// Affects the same functions as [`EngineConfig::delimiter`].
+    ///
+    /// Default: `true`.
+    pub has_headers: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            locked_deposits: false,
+            reject_duplicate_tx: true,
+            withdrawal_disputes: WithdrawalDisputePolicy::default(),
+            dispute_overdraft: DisputeOverdraftPolicy::default(),
+            excess_precision: ExcessPrecisionPolicy::default(),
+            max_transaction_amount: None,
+            reject_unknown_columns: false,
+            skip_unknown_types: false,
+            two_pass: false,
+            delimiter: b',',
+            has_headers: true,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Returns `true` if disputes can make the available funds
+    /// negative, i.e. if [`EngineConfig::dispute_overdraft`] is
+    /// [`DisputeOverdraftPolicy::AllowNegative`].
+    pub fn allow_negative_available(&self) -> bool {
+        self.dispute_overdraft == DisputeOverdraftPolicy::AllowNegative
+    }
+
+    /// Sets if disputes can make the available funds negative.
+    ///
+    /// Sets [`EngineConfig::dispute_overdraft`] to
+    /// [`DisputeOverdraftPolicy::AllowNegative`] (if `true`) or
+    /// [`DisputeOverdraftPolicy::Reject`] (if `false`, the default).
+    /// The previous policy is always replaced, i.e. `false` turns
+    /// [`DisputeOverdraftPolicy::ClampToZero`] into
+    /// [`DisputeOverdraftPolicy::Reject`] too.
+    pub fn set_allow_negative_available(&mut self, allow_negative_available: bool) {
+        self.dispute_overdraft = if allow_negative_available {
+            DisputeOverdraftPolicy::AllowNegative
+        } else {
+            DisputeOverdraftPolicy::Reject
+        };
+    }
+
+    /// Adjusts the amount of a deposit or withdrawal to four decimal
+    /// places according to [`ExcessPrecisionPolicy`].
+    ///
+    /// Fails with [`Error::TooPrecise`] if the amount is rejected.
+    fn check_precision(&self, tx: TxId, amount: Decimal) -> Result<Decimal> {
+        // trailing zeros do not count as excess precision
+        if amount.normalize().scale() <= DECIMAL_PLACES {
+            return Ok(amount);
+        }
+        let strategy = match self.excess_precision {
+            ExcessPrecisionPolicy::Reject => return Err(Error::TooPrecise(tx)),
+            ExcessPrecisionPolicy::Truncate => RoundingStrategy::ToZero,
+            ExcessPrecisionPolicy::RoundHalfEven => RoundingStrategy::MidpointNearestEven,
+        };
+        Ok(amount.round_dp_with_strategy(DECIMAL_PLACES, strategy))
+    }
+
+    /// Returns the builder of CSV readers of the input format, see
+    /// [`EngineConfig::delimiter`] and [`EngineConfig::has_headers`].
+    fn reader_builder(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .trim(Trim::All)
+            .flexible(true)
+            .delimiter(self.delimiter)
+            .has_headers(self.has_headers);
+        builder
+    }
+
+    /// Creates the CSV reader of the input returning it together with
+    /// the names of the columns.
+    ///
+    /// The header line is checked with [`check_headers`], inputs
+    /// without one have the [`COLUMNS`] in this order.
+    fn csv_reader<R: Read>(&self, reader: R) -> Result<(csv::Reader<R>, StringRecord)> {
+        let mut rdr = self.reader_builder().from_reader(reader);
+        let headers = if self.has_headers {
+            check_headers(rdr.headers()?, self.reject_unknown_columns)?
+        } else {
+            StringRecord::from(COLUMNS.as_slice())
+        };
+        Ok((rdr, headers))
+    }
+
+    /// Joins the fields of a record back into a line of the input.
+    fn join_fields(&self, fields: &StringRecord) -> String {
+        fields
+            .iter()
+            .collect::<Vec<_>>()
+            .join(&char::from(self.delimiter).to_string())
+    }
+
+    /// Validates the amount of a deposit or withdrawal returning the
+    /// amount that should be applied to the account.
+    ///
+    /// Fails with [`Error::Overflow`] if the amount cannot be converted
+    /// to the [`Amount`] type of the engine.
+    fn check_amount<A: Amount>(&self, tx: TxId, amount: Decimal) -> Result<A> {
+        let amount = self.check_precision(tx, amount)?;
+        if let Some(limit) = self.max_transaction_amount {
+            if amount > limit {
+                return Err(Error::AmountTooLarge { tx, amount, limit });
+            }
+        }
+        A::from_decimal(amount).ok_or(Error::Overflow(tx))
+    }
+}
+
+/// Options which configure how the records are processed.
+///
+/// The default options match the behavior of [`process`]:
+///
+/// ```
+/// use tx_engine::ProcessOptions;
+///
+/// let options = ProcessOptions::new().strict(true).locked_deposits(true);
+/// ```
+///
+/// The options are a builder of [`EngineConfig`], see its fields for
+/// detailed description of each option.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessOptions {
+    config: EngineConfig,
+    diagnostics: Option<Arc<dyn Diagnostics>>,
+}
+
+impl From<EngineConfig> for ProcessOptions {
+    fn from(config: EngineConfig) -> Self {
+        Self {
+            config,
+            diagnostics: None,
+        }
+    }
+}
+
+/// Specifies how disputes of withdrawals are handled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WithdrawalDisputePolicy {
+    /// Withdrawals cannot be disputed. Disputes referencing withdrawals
+    /// are logged and ignored (or return [`Error::NotDisputable`] in
+    /// strict mode).
+    #[default]
+    Ignore,
+
+    /// Disputed withdrawals are treated as potentially reversed.
+    ///
+    /// The dispute increases the held funds by the withdrawn amount
+    /// without touching the available funds. Resolving the dispute
+    /// removes the held funds (the withdrawal stands) while the
+    /// chargeback moves them back to available funds (the withdrawal
+    /// is reversed) and locks the account.
+    Reverse,
+}
+
+/// Specifies how disputes of deposits are handled when the available
+/// funds are lower than the disputed amount (e.g. because they have
+/// already been withdrawn).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeOverdraftPolicy {
+    /// The whole disputed amount is held even if the available funds
+    /// become negative.
+    AllowNegative,
+
+    /// Only the available funds are held. The available funds never go
+    /// below zero and the resolve or chargeback of the dispute uses the
+    /// amount that has actually been held.
+    ClampToZero,
+
+    /// The dispute is logged and ignored (or returns
+    /// [`Error::InsufficientFunds`] in strict mode).
+    #[default]
+    Reject,
+}
+
+/// Specifies how deposit and withdrawal amounts with more than four
+/// decimal places are handled.
+///
+/// The amounts are adjusted before they are applied to the account.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExcessPrecisionPolicy {
+    /// The transaction is logged and ignored (or returns
+    /// [`Error::TooPrecise`] in strict mode).
+    Reject,
+
+    /// Excess decimal places are dropped, e.g. `1.23456` becomes `1.2345`.
+    Truncate,
+
+    /// Amounts are rounded to four decimal places using banker's
+    /// rounding, e.g. `1.23456` becomes `1.2346` and `1.00005` becomes
+    /// `1.0000`.
+    #[default]
+    RoundHalfEven,
+}
+
+impl ProcessOptions {
+    /// Creates default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the engine configuration built by these options.
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    /// Enables strict mode.
+    ///
+    /// By default operations which cannot be performed (e.g. withdrawals
+    /// with insufficient funds) are logged and ignored. In strict mode
+    /// they return an error instead.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    /// Allows deposits to locked accounts.
+    ///
+    /// By default locked accounts do not accept any deposits or
+    /// withdrawals. Enabling this option allows deposits (but not
+    /// withdrawals).
+    pub fn locked_deposits(mut self, locked_deposits: bool) -> Self {
+        self.config.locked_deposits = locked_deposits;
+        self
+    }
+
+    /// Rejects deposits and withdrawals reusing an existing
+    /// transaction identifier.
+    ///
+    /// This option is enabled by default and such transactions are
+    /// logged and ignored (or return [`Error::DuplicateTx`] in strict
+    /// mode). If disabled, such transactions are applied to the account
+    /// but cannot be referenced by disputes, since disputes always
+    /// reference the first transaction with a given identifier.
+    pub fn reject_duplicate_tx(mut self, reject_duplicate_tx: bool) -> Self {
+        self.config.reject_duplicate_tx = reject_duplicate_tx;
+        self
+    }
+
+    /// Sets how disputes of withdrawals are handled.
+    ///
+    /// By default withdrawals cannot be disputed, see
+    /// [`WithdrawalDisputePolicy`].
+    pub fn withdrawal_disputes(mut self, policy: WithdrawalDisputePolicy) -> Self {
+        self.config.withdrawal_disputes = policy;
+        self
+    }
+
+    /// Sets how disputes exceeding available funds are handled.
+    ///
+    /// By default such disputes are rejected, see
+    /// [`DisputeOverdraftPolicy`].
+    pub fn dispute_overdraft(mut self, policy: DisputeOverdraftPolicy) -> Self {
+        self.config.dispute_overdraft = policy;
+        self
+    }
+
+    /// Sets if disputes can make the available funds negative.
+    ///
+    /// This is a shorthand for [`DisputeOverdraftPolicy::AllowNegative`]
+    /// (if `true`) and [`DisputeOverdraftPolicy::Reject`] (if `false`,
+    /// the default) replacing any previously set policy, see
+    /// [`EngineConfig::set_allow_negative_available`].
+    pub fn allow_negative_available(mut self, allow_negative_available: bool) -> Self {
+        self.config
+            .set_allow_negative_available(allow_negative_available);
+        self
+    }
+
+    /// Sets the maximum amount of a single deposit or withdrawal.
+    ///
+    /// By default the amounts are not limited.
+    pub fn max_transaction_amount(mut self, limit: Decimal) -> Self {
+        self.config.max_transaction_amount = Some(limit);
+        self
+    }
+
+    /// Rejects inputs containing unknown columns.
+    ///
+    /// By default unknown columns are ignored.
+    pub fn reject_unknown_columns(mut self, reject_unknown_columns: bool) -> Self {
+        self.config.reject_unknown_columns = reject_unknown_columns;
+        self
+    }
+
+    /// Skips records with unknown types instead of failing.
+    ///
+    /// By default records with unknown types fail to parse.
+    pub fn skip_unknown_types(mut self, skip_unknown_types: bool) -> Self {
+        self.config.skip_unknown_types = skip_unknown_types;
+        self
+    }
+
+    /// Applies disputes, resolves and chargebacks after all deposits and
+    /// withdrawals of each input.
+    ///
+    /// By default records are applied in the order of the input, see
+    /// [`EngineConfig::two_pass`].
+    pub fn two_pass(mut self, two_pass: bool) -> Self {
+        self.config.two_pass = two_pass;
+        self
+    }
+
+    /// Sets the character separating the fields of the input.
+    ///
+    /// By default fields are separated by commas.
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.config.delimiter = delimiter;
+        self
+    }
+
+    /// Sets whether the first line of the input names the columns.
+    ///
+    /// By default the header line is required, see
+    /// [`EngineConfig::has_headers`].
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.config.has_headers = has_headers;
+        self
+    }
+
+    /// Sets how amounts with more than four decimal places are handled.
+    ///
+    /// By default such amounts are rounded, see [`ExcessPrecisionPolicy`].
+    pub fn excess_precision(mut self, policy: ExcessPrecisionPolicy) -> Self {
+        self.config.excess_precision = policy;
+        self
+    }
+
+    /// Sets the receiver of rejections and other notable events.
+    ///
+    /// By default they are logged, see [`LogDiagnostics`].
+    pub fn diagnostics(mut self, diagnostics: Arc<dyn Diagnostics>) -> Self {
+        self.diagnostics = Some(diagnostics);
+        self
+    }
+}
+
+/// Kind of a transaction that is stored by the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TxKind {
+    /// Money deposit.
+    Deposit,
+
+    /// Money withdrawal.
+    Withdrawal,
+}
+
+/// State of a transaction that is stored by the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TxState {
+    /// Transaction has been applied and is not disputed.
+    Open,
+
+    /// Transaction is under dispute.
+    Disputed,
+
+    /// Dispute of the transaction has been resolved. The transaction
+    /// can be disputed again.
+    Resolved,
+
+    /// Transaction has been charged back. This state is terminal.
+    ChargedBack,
+}
+
+/// Transaction that can be referenced by disputes.
+///
+/// Stored transactions can be inspected with [`Engine::transaction`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StoredTx<A = Decimal> {
+    client: ClientId,
+    amount: A,
+    kind: TxKind,
+    state: TxState,
+    /// Amount held by the current or the last dispute.
+    held: A,
+}
+
+impl<A: Amount> StoredTx<A> {
+    /// Returns the client owning this transaction.
+    pub fn client(&self) -> ClientId {
+        self.client
+    }
+
+    /// Returns the amount of this transaction.
+    pub fn amount(&self) -> A {
+        self.amount
+    }
+
+    /// Returns whether this transaction is a deposit or a withdrawal.
+    pub fn kind(&self) -> TxKind {
+        self.kind
+    }
+
+    /// Returns the dispute state of this transaction.
+    pub fn state(&self) -> TxState {
+        self.state
+    }
+
+    /// Returns the amount held by the current or the last dispute of
+    /// this transaction.
+    ///
+    /// This may be lower than [`StoredTx::amount`] if
+    /// [`DisputeOverdraftPolicy::ClampToZero`] is used.
+    pub fn held(&self) -> A {
+        self.held
+    }
+
+    /// Checks if the transaction can be resolved or charged back.
+    fn check_disputed(&self, tx: TxId) -> Result<()> {
+        match self.state {
+            TxState::Disputed => Ok(()),
+            TxState::Open | TxState::Resolved => Err(Error::NotDisputed(tx)),
+            TxState::ChargedBack => Err(Error::ChargedBack(tx)),
+        }
+    }
+}
+
+/// Transaction engine.
+///
+/// Holds the state of all accounts together with the transactions that
+/// can be referenced by disputes. Records are fed one at a time using
+/// [`Engine::apply`] which makes it possible to drive the engine from
+/// any source, not only CSV files.
+///
+/// Funds are [`Decimal`] by default. Engines using a different
+/// [`Amount`] type are created with [`Engine::from`] or
+/// [`Engine::default`], e.g. `Engine::<f64>::from(config)`.
+#[derive(Debug, Default)]
+pub struct Engine<A: Amount = Decimal> {
+    config: EngineConfig,
+    accounts: HashMap<ClientId, Account<A>>,
+    txns: HashMap<TxId, StoredTx<A>>,
+    summaries: HashMap<ClientId, Summary>,
+    rejections: Vec<Rejection>,
+    skipped: Vec<SkippedRow>,
+    /// Number of the record being applied, within the current input.
+    row: u64,
+    sink: Option<EventSink>,
+    on_apply: Option<OnApply<A>>,
+    /// Receiver of diagnostics, [`LogDiagnostics`] if not set.
+    diagnostics: Option<Arc<dyn Diagnostics>>,
+    stats: EngineStats,
+}
+
+/// Observer notified about every record applied or rejected by the
+/// engine.
+///
+/// Both methods do nothing by default. The `()` observer, used by
+/// [`Engine::apply`], ignores all notifications.
+pub trait EngineObserver<A: Amount = Decimal> {
+    /// Called after the record has been applied.
+    ///
+    /// The account is the state of the client's account after applying
+    /// the record.
+    fn on_applied(&mut self, record: &Record, account: &Account<A>) {
+        let _ = (record, account);
+    }
+
+    /// Called when the record has been rejected.
+    fn on_rejected(&mut self, record: &Record, reason: &RejectionReason) {
+        let _ = (record, reason);
+    }
+}
+
+impl<A: Amount> EngineObserver<A> for () {}
+
+/// Function set by the user, e.g. [`Engine::set_event_sink`].
+struct Callback<F: ?Sized>(Box<F>);
+
+impl<F: ?Sized> std::fmt::Debug for Callback<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+/// Function receiving events, see [`Engine::set_event_sink`].
+type EventSink = Callback<dyn FnMut(&Event) + Send>;
+
+/// Function called after applying records, see [`Engine::set_on_apply`].
+type OnApply<A> = Callback<dyn FnMut(&Record, &Account<A>) + Send>;
+
+impl<A: Amount> From<ProcessOptions> for Engine<A> {
+    /// Creates a new engine with no accounts using given options.
+    fn from(options: ProcessOptions) -> Self {
+        Self {
+            diagnostics: options.diagnostics,
+            ..Self::from(options.config)
+        }
+    }
+}
+
+impl<A: Amount> From<EngineConfig> for Engine<A> {
+    /// Creates a new engine with no accounts using given configuration.
+    fn from(config: EngineConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+}
+
+impl Engine {
+    /// Creates a new engine with no accounts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new engine with no accounts using given options.
+    pub fn with_options(options: ProcessOptions) -> Self {
+        Self::from(options)
+    }
+
+    /// Creates a new engine with no accounts using given configuration.
+    pub fn with_config(config: EngineConfig) -> Self {
+        Self::from(config)
+    }
+
+    /// Returns the summary of records and accounts processed so far.
+    pub fn report(&self) -> ProcessReport {
+        let stats = &self.stats;
+        ProcessReport {
+            records: stats.records_by_type.values().sum::<u64>() + stats.unknown_types,
+            records_by_type: stats.records_by_type.clone(),
+            rejected_by_reason: stats.rejected_by_reason.clone(),
+            rejections: self.rejections.clone(),
+            skipped: self.skipped.clone(),
+            accounts: self.accounts.len(),
+            locked_accounts: self
+                .accounts
+                .values()
+                .filter(|account| account.locked)
+                .count(),
+            total: self
+                .accounts
+                .values()
+                .try_fold(Decimal::ZERO, |sum, account| {
+                    account.amounts.checked_total()?.checked_add(sum)
+                }),
+        }
+    }
+}
+
+impl<A: Amount> Engine<A> {
+    /// Applies a single record to the state of the engine.
+    ///
+    /// Operations which cannot be performed (e.g. withdrawals with
+    /// insufficient funds) are logged and ignored unless strict mode
+    /// is enabled. Malformed records (e.g. deposits without an amount)
+    /// always return an error.
+    pub fn apply(&mut self, record: Record) -> Result<()> {
+        self.apply_observed(record, &mut ())
+    }
+
+    /// Applies a single record notifying the observer about the
+    /// outcome.
+    ///
+    /// Works like [`Engine::apply`]. Records which return an error are
+    /// neither applied nor rejected thus the observer is not notified
+    /// about them.
+    pub fn apply_observed(
+        &mut self,
+        record: Record,
+        observer: &mut impl EngineObserver<A>,
+    ) -> Result<()> {
+        *self.stats.records_by_type.entry(record.kind).or_default() += 1;
+        match self.try_apply(&record) {
+            Ok(()) => {
+                self.stats.applied += 1;
+                let account = &self.accounts[&record.client];
+                observer.on_applied(&record, account);
+                if let Some(Callback(on_apply)) = &mut self.on_apply {
+                    on_apply(&record, account);
+                }
+                Ok(())
+            }
+            Err(error) if !self.config.strict => {
+                let Some(reason) = RejectionReason::of(&error) else {
+                    return Err(error);
+                };
+                observer.on_rejected(&record, &reason);
+                let rejection = Rejection {
+                    row: self.row,
+                    tx: record.tx,
+                    client: record.client,
+                    kind: record.kind,
+                    amount: record.amount,
+                    reason,
+                };
+                self.rejections.push(rejection.clone());
+                self.emit(Event::Rejected(rejection));
+                Ok(())
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Returns records rejected so far.
+    ///
+    /// Rejected records are the ones which have been logged and ignored
+    /// outside of strict mode. They are kept until the engine is
+    /// dropped.
+    pub fn rejections(&self) -> &[Rejection] {
+        &self.rejections
+    }
+
+    /// Returns rows skipped so far.
+    ///
+    /// Like rejections, skipped rows are kept until the engine is
+    /// dropped.
+    pub fn skipped(&self) -> &[SkippedRow] {
+        &self.skipped
+    }
+
+    /// Sets a function receiving all events of the engine.
+    ///
+    /// Events are reported to the diagnostics (see
+    /// [`ProcessOptions::diagnostics`]) regardless of the sink. Setting
+    /// a new sink replaces the previous one.
+    pub fn set_event_sink(&mut self, sink: impl FnMut(&Event) + Send + 'static) {
+        self.sink = Some(Callback(Box::new(sink)));
+    }
+
+    /// Sets a function called after every applied record.
+    ///
+    /// The function receives the record and the resulting state of the
+    /// client's account which makes it possible to build an audit trail
+    /// of all changes. Setting a new function replaces the previous
+    /// one, see [`Engine::apply_observed`] for an alternative which
+    /// does not need to be stored in the engine.
+    pub fn set_on_apply(&mut self, on_apply: impl FnMut(&Record, &Account<A>) + Send + 'static) {
+        self.on_apply = Some(Callback(Box::new(on_apply)));
+    }
+
+    /// Returns the receiver of diagnostics.
+    fn diagnostics(&self) -> &dyn Diagnostics {
+        self.diagnostics.as_deref().unwrap_or(&LogDiagnostics)
+    }
+
+    /// Counts the event, reports it to the diagnostics and passes it to
+    /// the sink.
+    fn emit(&mut self, event: Event) {
+        match &event {
+            Event::Rejected(rejection) => {
+                self.stats.rejected += 1;
+                *self
+                    .stats
+                    .rejected_by_reason
+                    .entry(rejection.reason)
+                    .or_default() += 1;
+            }
+            Event::UnexpectedAmount { .. } => self.stats.unexpected_amounts += 1,
+            Event::UnknownRecordType { .. } => self.stats.unknown_types += 1,
+        }
+        match &event {
+            Event::Rejected(rejection) => self.diagnostics().rejection(rejection),
+            Event::UnexpectedAmount { .. } | Event::UnknownRecordType { .. } => {
+                self.diagnostics().note(format_args!("{event}"))
+            }
+        }
+        if let Some(Callback(sink)) = &mut self.sink {
+            sink(&event);
+        }
+    }
+
+    /// Applies a single record returning all errors as if the strict
+    /// mode was enabled.
+    fn try_apply(&mut self, record: &Record) -> Result<()> {
+        match record.validate() {
+            // the amount is not used thus the record can still be applied
+            Err(Error::UnexpectedAmount { tx, kind }) if !self.config.strict => {
+                self.emit(Event::UnexpectedAmount {
+                    row: self.row,
+                    tx,
+                    kind,
+                });
+            }
+            result => result?,
+        }
+        let is_transaction = matches!(record.kind, RecordType::Deposit | RecordType::Withdrawal);
+        if !is_transaction && !self.accounts.contains_key(&record.client) {
+            // only deposits and withdrawals create accounts, transactions
+            // of a client without an account do not exist thus this fails
+            // without creating an empty account
+            referenced(&mut self.txns, record)?;
+        }
+        let config = &self.config;
+        // new accounts are only stored once the record has been applied
+        // thus a rejected record does not leave an empty account behind
+        let mut created = None;
+        let account = match self.accounts.get_mut(&record.client) {
+            Some(account) => account,
+            None => created.insert(Account {
+                client: record.client,
+                ..Default::default()
+            }),
+        };
+        let mut created_summary = None;
+        let summary = match self.summaries.get_mut(&record.client) {
+            Some(summary) => summary,
+            None => created_summary.insert(Summary::default()),
+        };
+        match record.kind {
+            RecordType::Deposit => {
+                let Some(amount) = record.amount else {
+                    return Err(Error::DepositNoAmount(record.tx));
+                };
+                let amount = config.check_amount(record.tx, amount)?;
+                if account.is_locked() && !config.locked_deposits {
+                    return Err(Error::AccountLocked {
+                        client: record.client,
+                        tx: record.tx,
+                    });
+                }
+                if config.reject_duplicate_tx && self.txns.contains_key(&record.tx) {
+                    return Err(Error::DuplicateTx(record.tx));
+                }
+                account
+                    .amounts
+                    .deposit(amount)
+                    .map_err(|error| error.into_error(record.tx))?;
+                self.txns.entry(record.tx).or_insert(StoredTx {
+                    client: record.client,
+                    amount,
+                    kind: TxKind::Deposit,
+                    state: TxState::Open,
+                    held: A::ZERO,
+                });
+                summary.deposits += 1;
+            }
+            RecordType::Withdrawal => {
+                let Some(amount) = record.amount else {
+                    return Err(Error::WithdrawNoAmount(record.tx));
+                };
+                let amount = config.check_amount(record.tx, amount)?;
+                if account.is_locked() {
+                    return Err(Error::AccountLocked {
+                        client: record.client,
+                        tx: record.tx,
+                    });
+                }
+                if config.reject_duplicate_tx && self.txns.contains_key(&record.tx) {
+                    return Err(Error::DuplicateTx(record.tx));
+                }
+                account
+                    .amounts
+                    .withdraw(amount)
+                    .map_err(|error| error.into_error(record.tx))?;
+                self.txns.entry(record.tx).or_insert(StoredTx {
+                    client: record.client,
+                    amount,
+                    kind: TxKind::Withdrawal,
+                    state: TxState::Open,
+                    held: A::ZERO,
+                });
+                summary.withdrawals += 1;
+            }
+            RecordType::Dispute => {
+                let tx = referenced(&mut self.txns, record)?;
+                if tx.kind == TxKind::Withdrawal
+                    && config.withdrawal_disputes == WithdrawalDisputePolicy::Ignore
+                {
+                    return Err(Error::NotDisputable(record.tx));
+                }
+                match tx.state {
+                    TxState::Open | TxState::Resolved => {}
+                    TxState::Disputed => {
+                        return Err(Error::AlreadyDisputed(record.tx));
+                    }
+                    TxState::ChargedBack => {
+                        return Err(Error::ChargedBack(record.tx));
+                    }
+                }
+                let held = match (tx.kind, config.dispute_overdraft) {
+                    (TxKind::Deposit, DisputeOverdraftPolicy::AllowNegative) => {
+                        account.amounts.hold(tx.amount)
+                    }
+                    (TxKind::Deposit, DisputeOverdraftPolicy::ClampToZero) => {
+                        account.amounts.hold_clamped(tx.amount)
+                    }
+                    (TxKind::Deposit, DisputeOverdraftPolicy::Reject) => {
+                        account.amounts.try_hold(tx.amount).map(|()| tx.amount)
+                    }
+                    (TxKind::Withdrawal, _) => {
+                        account.amounts.hold_reversal(tx.amount).map(|()| tx.amount)
+                    }
+                };
+                tx.held = held.map_err(|error| error.into_error(record.tx))?;
+                tx.state = TxState::Disputed;
+                account.disputes_opened += 1;
+                summary.disputes += 1;
+            }
+            RecordType::Resolve => {
+                let tx = referenced(&mut self.txns, record)?;
+                tx.check_disputed(record.tx)?;
+                let result = match tx.kind {
+                    TxKind::Deposit => account.amounts.release(tx.held),
+                    // the withdrawal stands
+                    TxKind::Withdrawal => account.amounts.chargeback(tx.held),
+                };
+                result.map_err(|error| error.into_error(record.tx))?;
+                tx.state = TxState::Resolved;
+                account.disputes_resolved += 1;
+                summary.resolves += 1;
+            }
+            RecordType::Chargeback => {
+                let tx = referenced(&mut self.txns, record)?;
+                tx.check_disputed(record.tx)?;
+                let result = match tx.kind {
+                    TxKind::Deposit => account.amounts.chargeback(tx.held),
+                    // the withdrawal is reversed
+                    TxKind::Withdrawal => account.amounts.release(tx.held),
+                };
+                result.map_err(|error| error.into_error(record.tx))?;
+                // "frozen" means "locked == true"
+                if !account.locked {
+                    account.locked_by = Some(record.tx);
+                }
+                account.locked = true;
+                tx.state = TxState::ChargedBack;
+                account.chargebacks += 1;
+                summary.chargebacks += 1;
+            }
+        }
+        if let Some(account) = created {
+            self.accounts.insert(record.client, account);
+        }
+        if let Some(summary) = created_summary {
+            self.summaries.insert(record.client, summary);
+        }
+        Ok(())
+    }
+
+    /// Applies all records from CSV data coming from the reader.
+    ///
+    /// The values will be stripped of whitespace. Columns are matched by
+    /// the names in the header line thus they can be in any order and
+    /// unknown columns are ignored (unless
+    /// [`EngineConfig::reject_unknown_columns`] is set). Rows may also
+    /// have more fields than the header (e.g. a trailing timestamp
+    /// without a column name).
+    ///
+    /// The `transaction_type`, `client_id` and `tx_id` column names are
+    /// accepted as aliases of `type`, `client` and `tx`.
+    ///
+    /// If any of the required columns is missing
+    /// [`Error::MissingColumns`] is returned before processing any
+    /// records. Inputs without the header line can be processed by
+    /// disabling [`EngineConfig::has_headers`].
+    ///
+    /// If [`EngineConfig::two_pass`] is set, disputes, resolves and
+    /// chargebacks are applied after reaching the end of the input.
+    pub fn process_reader<R: Read>(&mut self, reader: R) -> Result<()> {
+        self.process_reader_observed(reader, &mut ())
+    }
+
+    /// Applies all records from CSV data coming from the reader
+    /// notifying the observer about each of them.
+    ///
+    /// Works like [`Engine::process_reader`], see
+    /// [`Engine::apply_observed`] for details about notifications.
+    pub fn process_reader_observed<R: Read>(
+        &mut self,
+        reader: R,
+        observer: &mut impl EngineObserver<A>,
+    ) -> Result<()> {
+        let (mut rdr, headers) = self.config.csv_reader(reader)?;
+        let type_column = headers.iter().position(|header| header == "type");
+
+        let mut fields = StringRecord::new();
+        let mut row = 0;
+        let mut deferred = Vec::new();
+        while rdr
+            .read_record(&mut fields)
+            .map_err(|error| parse_error(error, row + 1))?
+        {
+            row += 1;
+            if self.config.skip_unknown_types {
+                let value = type_column.and_then(|column| fields.get(column));
+                if let Some(value) = value.filter(|value| RecordType::from_name(value).is_none()) {
+                    let value = value.into();
+                    if self.config.strict {
+                        return Err(Error::UnknownRecordType { row, value });
+                    }
+                    self.emit(Event::UnknownRecordType { row, value });
+                    self.skipped.push(SkippedRow {
+                        row,
+                        raw: self.config.join_fields(&fields),
+                    });
+                    continue;
+                }
+            }
+            let record = deserialize_record(&fields, &headers, row)?;
+            let byte = fields.position().map(csv::Position::byte);
+            if self.config.two_pass
+                && !matches!(record.kind, RecordType::Deposit | RecordType::Withdrawal)
+            {
+                deferred.push((row, byte, record));
+                continue;
+            }
+            self.apply_at(row, byte, record, observer)?;
+        }
+        for (row, byte, record) in deferred {
+            self.apply_at(row, byte, record, observer)?;
+        }
+        self.row = 0;
+        Ok(())
+    }
+
+    /// Applies a record read from the input wrapping errors in
+    /// [`Error::AtRow`].
+    fn apply_at(
+        &mut self,
+        row: u64,
+        byte: Option<u64>,
+        record: Record,
+        observer: &mut impl EngineObserver<A>,
+    ) -> Result<()> {
+        self.row = row;
+        self.apply_observed(record, observer)
+            .map_err(|error| Error::AtRow {
+                row,
+                byte,
+                source: Box::new(error),
+            })
+    }
+
+    /// Applies all records from the CSV file.
+    ///
+    /// The state of the engine is preserved between calls thus files
+    /// can be processed one after another, e.g. disputes in one file
+    /// can reference transactions from previously processed files.
+    ///
+    /// Compressed files are transparently decompressed, see [`process`].
+    pub fn process_file(&mut self, file: impl AsRef<Path>) -> Result<()> {
+        in_file(file.as_ref(), |reader| self.process_reader(reader))
+    }
+
+    /// Returns accounts processed so far.
+    pub fn accounts(&self) -> &HashMap<ClientId, Account<A>> {
+        &self.accounts
+    }
+
+    /// Returns the deposit or withdrawal with given identifier.
+    ///
+    /// Rejected transactions are not stored and thus `None` is returned
+    /// for them.
+    pub fn transaction(&self, tx: TxId) -> Option<&StoredTx<A>> {
+        self.txns.get(&tx)
+    }
+
+    /// Returns transactions which are currently under dispute together
+    /// with their clients and the amounts held because of them.
+    ///
+    /// The order of the transactions is not specified. The held amount
+    /// may be lower than the amount of the transaction, see
+    /// [`DisputeOverdraftPolicy::ClampToZero`].
+    pub fn open_disputes(&self) -> impl Iterator<Item = (ClientId, TxId, A)> + '_ {
+        self.txns
+            .iter()
+            .filter(|(_, tx)| tx.state == TxState::Disputed)
+            .map(|(id, tx)| (tx.client, *id, tx.held))
+    }
+
+    /// Returns transaction counts of accounts processed so far.
+    pub fn summaries(&self) -> &HashMap<ClientId, Summary> {
+        &self.summaries
+    }
+
+    /// Returns numbers of records processed so far.
+    pub fn stats(&self) -> &EngineStats {
+        &self.stats
+    }
+
+    /// Consumes the engine returning all accounts.
+    pub fn into_accounts(self) -> HashMap<ClientId, Account<A>> {
+        self.accounts
+    }
+
+    /// Consumes the engine returning all stored transactions.
+    ///
+    /// The ledger contains every applied deposit and withdrawal together
+    /// with its client, amount and dispute state, e.g. to reconcile the
+    /// results with another system. Rejected transactions are not
+    /// stored, see [`Engine::transaction`].
+    pub fn into_ledger(self) -> HashMap<TxId, StoredTx<A>> {
+        self.txns
+    }
+}
+
+impl<A: Amount> Extend<Record> for Engine<A> {
+    /// Applies all records in order.
+    ///
+    /// Since errors cannot be returned records which fail to apply
+    /// (e.g. deposits without an amount) are logged and skipped.
+    fn extend<T: IntoIterator<Item = Record>>(&mut self, iter: T) {
+        for record in iter {
+            let tx = record.tx;
+            if let Err(error) = self.apply(record) {
+                self.diagnostics()
+                    .note(format_args!("Skipping transaction {tx}: {error}"));
+            }
+        }
+    }
+}
+
+impl<A: Amount> FromIterator<Record> for Engine<A> {
+    /// Creates an engine with default options and applies all records.
+    ///
+    /// See [`Engine::extend`] for the description of error handling.
+    fn from_iter<T: IntoIterator<Item = Record>>(iter: T) -> Self {
+        let mut engine = Self::default();
+        engine.extend(iter);
+        engine
+    }
+}
+
+/// Columns which are required in the input.
+const COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Alternative names of the columns, e.g. used by other systems.
+///
+/// Needs to be kept in sync with the `serde` aliases of [`Record`].
+const COLUMN_ALIASES: [(&str, &str); 3] = [
+    ("transaction_type", "type"),
+    ("client_id", "client"),
+    ("tx_id", "tx"),
+];
+
+/// Checks that the header line contains all required columns.
+///
+/// Returns the header line with aliases (see [`COLUMN_ALIASES`])
+/// replaced by the names of the columns. Empty inputs, without even the
+/// header line, are accepted since they do not contain any records.
+fn check_headers(headers: &StringRecord, reject_unknown: bool) -> Result<StringRecord> {
+    let headers = headers
+        .iter()
+        .map(|header| {
+            COLUMN_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == header)
+                .map_or(header, |(_, column)| column)
+        })
+        .collect::<StringRecord>();
+    if headers.is_empty() {
+        return Ok(headers);
+    }
+    let missing = COLUMNS
+        .iter()
+        .filter(|column| !headers.iter().any(|header| header == **column))
+        .map(|column| column.to_string())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(Error::MissingColumns(missing));
+    }
+    if reject_unknown {
+        let unknown = headers
+            .iter()
+            .filter(|header| !COLUMNS.contains(header))
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            return Err(Error::UnknownColumns(unknown));
+        }
+    }
+    Ok(headers)
+}
+
+/// Converts CSV errors which have a position to [`Error::Parse`] of
+/// the record with given number.
+///
+/// The number of the record is passed explicitly since the position
+/// counts the header line as a record (if there is one).
+fn parse_error(error: csv::Error, row: u64) -> Error {
+    match error.position() {
+        Some(position) => Error::Parse {
+            line: position.line(),
+            row,
+            source: error,
+        },
+        None => Error::Csv(error),
+    }
+}
+
+/// Deserializes the record from its fields.
+///
+/// Errors in the `client`, `tx` and `amount` columns are converted to
+/// [`Error::InvalidField`], other errors to [`Error::Parse`].
+fn deserialize_record(fields: &StringRecord, headers: &StringRecord, row: u64) -> Result<Record> {
+    fields.deserialize(Some(headers)).map_err(|error| {
+        invalid_field(&error, fields, headers, row).unwrap_or_else(|| parse_error(error, row))
+    })
+}
+
+/// Converts deserialization errors of the `client`, `tx` and `amount`
+/// columns to [`Error::InvalidField`].
+fn invalid_field(
+    error: &csv::Error,
+    fields: &StringRecord,
+    headers: &StringRecord,
+    row: u64,
+) -> Option<Error> {
+    let csv::ErrorKind::Deserialize { err, .. } = error.kind() else {
+        return None;
+    };
+    let index = match err.field() {
+        Some(index) => usize::try_from(index).ok()?,
+        // errors of the amount, which is parsed separately, do not
+        // carry the index of the field
+        None => {
+            let index = headers.iter().position(|header| header == "amount")?;
+            let value = fields.get(index).filter(|value| !value.is_empty())?;
+            parse_amount(value).err()?;
+            index
+        }
+    };
+    let column = COLUMNS[1..]
+        .iter()
+        .find(|column| headers.get(index) == Some(column))?;
+    Some(Error::InvalidField {
+        row,
+        column,
+        value: fields.get(index)?.into(),
+    })
+}
+
+/// Returns the transaction referenced by the record.
+///
+/// Fails if the transaction does not exist or if it belongs to a
+/// different client than the one in the record.
+fn referenced<'a, A>(
+    txns: &'a mut HashMap<TxId, StoredTx<A>>,
+    record: &Record,
+) -> Result<&'a mut StoredTx<A>> {
+    let Some(tx) = txns.get_mut(&record.tx) else {
+        return Err(Error::TxNotFound(record.tx));
+    };
+    if tx.client != record.client {
+        return Err(Error::ClientMismatch {
+            tx: record.tx,
+            expected: tx.client,
+            got: record.client,
+        });
+    }
+    Ok(tx)
+}
+
+/// First bytes of every gzip-compressed file.
+#[cfg(feature = "compression")]
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Opens the input file decompressing it if it has the `.gz` extension
+/// or starts with the gzip magic bytes.
+#[cfg(feature = "compression")]
+fn open(file: &Path) -> Result<Box<dyn Read>> {
+    use std::io::BufRead;
+
+    let mut reader = std::io::BufReader::new(File::open(file)?);
+    if file.extension().is_some_and(|extension| extension == "gz")
+        || reader.fill_buf()?.starts_with(&GZIP_MAGIC)
+    {
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Opens the input file.
+#[cfg(not(feature = "compression"))]
+fn open(file: &Path) -> Result<Box<dyn Read>> {
+    Ok(Box::new(File::open(file)?))
+}
+
+/// Opens and processes the file wrapping all errors in
+/// [`Error::InFile`].
+fn in_file<T>(file: &Path, process: impl FnOnce(Box<dyn Read>) -> Result<T>) -> Result<T> {
+    open(file).and_then(process).map_err(|error| Error::InFile {
+        path: file.into(),
+        source: Box::new(error),
+    })
+}
+
+/// Process the input CSV file.
+///
+/// The input file will have the values stripped of whitespace. Files
+/// with the `.gz` extension or starting with the gzip magic bytes are
+/// transparently decompressed, unless the `compression` feature is
+/// disabled.
+pub fn process(file: impl AsRef<Path>) -> Result<HashMap<ClientId, Account>> {
+    process_with(file, &ProcessOptions::default())
+}
+
+/// Process the input CSV file using given options.
+///
+/// See [`ProcessOptions`] for the description of available options.
+pub fn process_with(
+    file: impl AsRef<Path>,
+    options: &ProcessOptions,
+) -> Result<HashMap<ClientId, Account>> {
+    in_file(file.as_ref(), |reader| process_reader_with(reader, options))
+}
+
+/// Process CSV data coming from any reader.
+///
+/// This works the same way as [`process`] but does not require the
+/// input to be stored on disk. The values will be stripped of whitespace.
+///
+/// An empty input (not even a header line) is not an error: it
+/// contains no records and thus results in no accounts.
+pub fn process_reader<R: Read>(reader: R) -> Result<HashMap<ClientId, Account>> {
+    process_reader_with(reader, &ProcessOptions::default())
+}
+
+/// Process transactions from the CSV file returning accounts together
+/// with numbers of transactions applied to each of them.
+///
+/// The summaries make it easy to spot accounts with unusual activity,
+/// e.g. with many disputes.
+pub fn process_with_summary(
+    file: impl AsRef<Path>,
+) -> Result<(HashMap<ClientId, Account>, HashMap<ClientId, Summary>)> {
+    let mut engine = Engine::new();
+    engine.process_file(file)?;
+    let summaries = std::mem::take(&mut engine.summaries);
+    Ok((engine.into_accounts(), summaries))
+}
+
+/// Process multiple CSV files in order.
+///
+/// The files share the state of the engine thus disputes in one file
+/// can reference transactions from any previous file.
+pub fn process_many<I, P>(files: I) -> Result<HashMap<ClientId, Account>>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut engine = Engine::new();
+    for file in files {
+        engine.process_file(file)?;
+    }
+    Ok(engine.into_accounts())
+}
+
+/// Process the input CSV file skipping rows which cannot be processed.
+///
+/// Malformed rows as well as deposits and withdrawals without an amount
+/// do not abort the processing. Instead they are collected and returned
+/// alongside the accounts. Errors which prevent further processing
+/// (e.g. the file cannot be opened or read) are still returned as `Err`.
+pub fn process_lenient(
+    file: impl AsRef<Path>,
+) -> Result<(HashMap<ClientId, Account>, Vec<RowError>)> {
+    in_file(file.as_ref(), process_reader_lenient)
+}
+
+/// Process CSV data coming from any reader skipping rows which cannot
+/// be processed.
+///
+/// See [`process_lenient`] for the description of error handling.
+pub fn process_reader_lenient<R: Read>(
+    reader: R,
+) -> Result<(HashMap<ClientId, Account>, Vec<RowError>)> {
+    process_reader_lenient_with(reader, &ProcessOptions::default())
+}
+
+/// Process CSV data coming from any reader using given options
+/// skipping rows which cannot be processed.
+///
+/// See [`process_lenient`] for the description of error handling. The
+/// records are always applied in the order of the input and rows of
+/// unknown types are returned as errors, i.e.
+/// [`EngineConfig::two_pass`] and [`EngineConfig::skip_unknown_types`]
+/// are not supported.
+pub fn process_reader_lenient_with<R: Read>(
+    reader: R,
+    options: &ProcessOptions,
+) -> Result<(HashMap<ClientId, Account>, Vec<RowError>)> {
+    let mut engine = Engine::with_options(options.clone());
+    let (mut rdr, headers) = engine.config.csv_reader(reader)?;
+
+    let mut errors = vec![];
+    for (row, fields) in (1..).zip(rdr.records()) {
+        let fields = match fields {
+            Ok(fields) => fields,
+            Err(error) if error.is_io_error() => return Err(error.into()),
+            Err(error) => {
+                errors.push(RowError {
+                    record: row,
+                    line: None,
+                    error: error.into(),
+                });
+                continue;
+            }
+        };
+        engine.row = row;
+        let result =
+            deserialize_record(&fields, &headers, row).and_then(|record| engine.apply(record));
+        if let Err(error) = result {
+            errors.push(RowError {
+                record: row,
+                line: Some(engine.config.join_fields(&fields)),
+                error,
+            });
+        }
+    }
+    Ok((engine.into_accounts(), errors))
+}
+
+/// Process the input CSV file notifying the observer about every
+/// applied and rejected record.
+///
+/// This makes it possible to follow the decisions of the engine while
+/// the input is being processed, see [`EngineObserver`].
+pub fn process_with_observer(
+    file: impl AsRef<Path>,
+    observer: &mut impl EngineObserver,
+) -> Result<HashMap<ClientId, Account>> {
+    let mut engine = Engine::new();
+    in_file(file.as_ref(), |reader| {
+        engine.process_reader_observed(reader, observer)
+    })?;
+    Ok(engine.into_accounts())
+}
+
+/// Process the input CSV file returning accounts together with the
+/// summary of the processing.
+///
+/// The summary includes the records which have been rejected, in the
+/// order of the records in the input.
+pub fn process_with_report(
+    file: impl AsRef<Path>,
+) -> Result<(HashMap<ClientId, Account>, ProcessReport)> {
+    let mut engine = Engine::new();
+    engine.process_file(file)?;
+    let report = engine.report();
+    Ok((engine.into_accounts(), report))
+}
+
+/// Process CSV data coming from any reader using given options.
+///
+/// See [`ProcessOptions`] for the description of available options.
+pub fn process_reader_with<R: Read>(
+    reader: R,
+    options: &ProcessOptions,
+) -> Result<HashMap<ClientId, Account>> {
+    let mut engine = Engine::with_options(options.clone());
+    engine.process_reader(reader)?;
+    Ok(engine.into_accounts())
+}
+
+/// Process CSV data coming from any reader computing the account of
+/// a single client.
+///
+/// Records of other clients are skipped without being fully parsed
+/// which makes this function faster than [`process_reader`] when
+/// investigating a single account. Since transactions of other clients
+/// are not considered the result may differ from the one of
+/// [`process_reader`] only if transaction identifiers are reused
+/// across clients. Returns `None` if the input has no records of the
+/// client.
+pub fn process_client<R: Read>(reader: R, client: ClientId) -> Result<Option<Account>> {
+    process_client_with(reader, client, &ProcessOptions::default())
+}
+
+/// Process CSV data coming from any reader using given options
+/// computing the account of a single client.
+///
+/// Works like [`process_client`]. The records are always applied in
+/// the order of the input, i.e. [`EngineConfig::two_pass`] and
+/// [`EngineConfig::skip_unknown_types`] are not supported.
+pub fn process_client_with<R: Read>(
+    reader: R,
+    client: ClientId,
+    options: &ProcessOptions,
+) -> Result<Option<Account>> {
+    let mut engine = Engine::with_options(options.clone());
+    let (mut rdr, headers) = engine.config.csv_reader(reader)?;
+    let client_column = headers.iter().position(|header| header == "client");
+
+    let mut fields = StringRecord::new();
+    let mut row = 0;
+    while rdr
+        .read_record(&mut fields)
+        .map_err(|error| parse_error(error, row + 1))?
+    {
+        row += 1;
+        let other = client_column
+            .and_then(|column| fields.get(column))
+            .and_then(|value| value.parse::<ClientId>().ok())
+            .is_some_and(|value| value != client);
+        if other {
+            continue;
+        }
+        let record = deserialize_record(&fields, &headers, row)?;
+        engine.row = row;
+        engine.apply(record).map_err(|error| Error::AtRow {
+            row,
+            byte: fields.position().map(csv::Position::byte),
+            source: Box::new(error),
+        })?;
+    }
+    Ok(engine.accounts.remove(&client))
+}
+
+/// Process records that have already been parsed.
+///
+/// The records are applied in order without any CSV involvement which
+/// makes this function useful for generating inputs programmatically.
+pub fn process_records<I: IntoIterator<Item = Record>>(
+    records: I,
+) -> Result<HashMap<ClientId, Account>> {
+    let mut engine = Engine::new();
+    for record in records {
+        engine.apply(record)?;
+    }
+    Ok(engine.into_accounts())
+}
+
+/// Returns accounts sorted by their client identifiers.
+///
+/// The accounts are always in ascending order of the identifiers, each
+/// client appearing once. Iteration order of a [`HashMap`] is not
+/// specified so this function should be used when the output needs to
+/// be deterministic.
+pub fn sorted_accounts(accounts: HashMap<ClientId, Account>) -> Vec<Account> {
+    let mut accounts = accounts.into_values().collect::<Vec<_>>();
+    accounts.sort_unstable_by_key(|account| account.client);
+    accounts
+}
+
+/// Names of the columns of the CSV output.
+const OUTPUT_COLUMNS: [&str; 5] = ["client", "available", "held", "total", "locked"];
+
+/// Writes accounts in CSV format.
+///
+/// The header line (`client,available,held,total,locked`) is always
+/// written, even if there are no accounts. The accounts are written in
+/// the order of the iterator, see [`sorted_accounts`], with amounts
+/// rounded using the default [`Precision`].
+pub fn write_accounts<'a, W: Write>(
+    writer: W,
+    accounts: impl IntoIterator<Item = &'a Account>,
+) -> Result<()> {
+    write_accounts_with(writer, accounts, OutputOptions::default())
+}
+
+/// Writes accounts in CSV format using given options, e.g. the
+/// precision of the amounts.
+///
+/// Works like [`write_accounts`].
+pub fn write_accounts_with<'a, W: Write>(
+    writer: W,
+    accounts: impl IntoIterator<Item = &'a Account>,
+    options: OutputOptions,
+) -> Result<()> {
+    let mut writer = options.writer(writer);
+    writer.write_record(OUTPUT_COLUMNS)?;
+    for account in accounts {
+        writer.serialize(account.rounded(options.precision))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Names of the additional columns written by
+/// [`write_accounts_extended`].
+const EXTENDED_COLUMNS: [&str; 4] = [
+    "disputes_opened",
+    "disputes_resolved",
+    "chargebacks",
+    "locked_by",
+];
+
+/// Writes accounts in CSV format together with their dispute counts.
+///
+/// In addition to the columns written by [`write_accounts`] the
+/// numbers of disputes, resolved disputes and chargebacks as well as
+/// the chargeback which has locked the account (empty if the account
+/// is not locked) are written, see [`Account::disputes_opened`].
+pub fn write_accounts_extended<'a, W: Write>(
+    writer: W,
+    accounts: impl IntoIterator<Item = &'a Account>,
+    options: OutputOptions,
+) -> Result<()> {
+    let precision = options.precision;
+    let mut writer = options.writer(writer);
+    writer.write_record(OUTPUT_COLUMNS.iter().chain(&EXTENDED_COLUMNS))?;
+    for account in accounts {
+        let total = account.amounts.checked_total().ok_or(Error::Invariant {
+            client: account.client,
+            reason: "total amount overflows",
+        })?;
+        writer.serialize((
+            account.client,
+            precision.round(account.amounts.available),
+            precision.round(account.amounts.held),
+            precision.round(total),
+            account.locked,
+            account.disputes_opened,
+            account.disputes_resolved,
+            account.chargebacks,
+            account.locked_by,
+        ))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes accounts as a JSON array followed by a new line.
+///
+/// Each account is an object with the same fields as the CSV output of
+/// [`write_accounts_with`]: `client`, `available`, `held`, `total` and
+/// `locked`, with amounts rounded using given precision. Amounts are
+/// serialized as strings so that they are not converted to floating
+/// point numbers. The accounts are written in the order of the
+/// iterator, see [`sorted_accounts`].
+pub fn write_accounts_json<'a, W: Write>(
+    mut writer: W,
+    accounts: impl IntoIterator<Item = &'a Account>,
+    precision: Precision,
+) -> Result<()> {
+    let accounts = accounts
+        .into_iter()
+        .map(|account| account.rounded(precision))
+        .collect::<Vec<_>>();
+    serde_json::to_writer(&mut writer, &accounts)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Names of the columns written by [`write_rejections`].
+const REJECTION_COLUMNS: [&str; 7] = ["row", "type", "client", "tx", "amount", "reason", "raw"];
+
+/// Writes rejected records and skipped rows of the report in CSV format.
+///
+/// Rejected records are written first, followed by skipped rows, both
+/// in the order of the input. The `raw` column is empty for rejected
+/// records while skipped rows have only the `row`, `reason` (always
+/// `unknown_record_type`) and `raw` columns. Amounts are written
+/// without trailing zeros. The header line is always written.
+pub fn write_rejections<W: Write>(writer: W, report: &ProcessReport) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(REJECTION_COLUMNS)?;
+    for rejection in &report.rejections {
+        writer.write_record([
+            rejection.row.to_string(),
+            rejection.kind.to_string(),
+            rejection.client.to_string(),
+            rejection.tx.to_string(),
+            rejection
+                .amount
+                .map(|amount| amount.to_string())
+                .unwrap_or_default(),
+            rejection.reason.code().into(),
+            String::new(),
+        ])?;
+    }
+    for skipped in &report.skipped {
+        writer.write_record([
+            &skipped.row.to_string(),
+            "",
+            "",
+            "",
+            "",
+            "unknown_record_type",
+            &skipped.raw,
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Number of accounts after which [`write_accounts_jsonl`] flushes the
+/// writer.
+const JSONL_FLUSH_INTERVAL: usize = 1024;
+
+/// Writes accounts as JSON objects, one per line.
+///
+/// The objects are the same as the ones written by
+/// [`write_accounts_json`].
+/// The accounts are written in the order of the iterator and the writer
+/// is flushed periodically so that consumers can read them while they
+/// are being written.
+pub fn write_accounts_jsonl<'a, W: Write>(
+    mut writer: W,
+    accounts: impl IntoIterator<Item = &'a Account>,
+    precision: Precision,
+) -> Result<()> {
+    for (index, account) in accounts.into_iter().enumerate() {
+        serde_json::to_writer(&mut writer, &account.rounded(precision))?;
+        writer.write_all(b"\n")?;
+        if (index + 1) % JSONL_FLUSH_INTERVAL == 0 {
+            writer.flush()?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Process CSV data from the reader and write resulting accounts to the writer.
+///
+/// The accounts are written in CSV format with a header line, sorted
+/// by client identifiers.
+pub fn process_into_writer(input: impl Read, out: impl Write) -> Result<()> {
+    let accounts = process_reader(input)?;
+    write_accounts(out, &sorted_accounts(accounts))
+}
+
+/// Process CSV data stored in a string.
+///
+/// This is mostly useful for tests and small, embedded inputs. The
+/// values will be stripped of whitespace like in [`process`].
+pub fn process_str(input: &str) -> Result<HashMap<ClientId, Account>> {
+    process_reader(Cursor::new(input.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use testresult::TestResult;
+
+    use super::*;
+
+    #[test]
+    fn withdraw_ok() -> TestResult {
+        let mut a = Amounts::default();
+        a.deposit(2.into())?;
+        a.withdraw(1.into())?;
+        assert_eq!(a.available, 1.into());
+        assert_eq!(a.held, 0.into());
+        Ok(())
+    }
+
+    #[test]
+    fn withdraw_failed() -> TestResult {
+        let mut a = Amounts::default();
+        a.deposit(1.into())?;
+        assert_eq!(a.withdraw(2.into()), Err(AmountsError::InsufficientFunds));
+        assert_eq!(a.available, 1.into());
+        assert_eq!(a.held, 0.into());
+        Ok(())
+    }
+
+    #[test]
+    fn try_hold_ok() -> TestResult {
+        let mut a = Amounts::default();
+        a.deposit(2.into())?;
+        a.try_hold(2.into())?;
+        assert_eq!(a.available, 0.into());
+        assert_eq!(a.held, 2.into());
+        Ok(())
+    }
+
+    #[test]
+    fn try_hold_failed() -> TestResult {
+        let mut a = Amounts::default();
+        a.deposit(1.into())?;
+        assert_eq!(a.try_hold(2.into()), Err(AmountsError::InsufficientFunds));
+        assert_eq!(a.available, 1.into());
+        assert_eq!(a.held, 0.into());
+        Ok(())
     }
 
-    /// Completes the chargeback procedure removing held funds from this account.
-    pub fn chargeback(&mut self, amount: Decimal) {
-        self.held -= amount;
+    #[test]
+    fn hold_clamped() -> TestResult {
+        let mut a = Amounts::default();
+        a.deposit(1.into())?;
+        assert_eq!(a.hold_clamped(3.into())?, 1.into());
+        assert_eq!(a.available, 0.into());
+        assert_eq!(a.held, 1.into());
+
+        assert_eq!(a.hold_clamped(3.into())?, 0.into());
+        assert_eq!(a.held, 1.into());
+
+        let mut a = Amounts {
+            available: (-1).into(),
+            held: 0.into(),
+        };
+        assert_eq!(a.hold_clamped(3.into())?, 0.into());
+        assert_eq!(a.available, (-1).into());
+        Ok(())
     }
 
-    /// Returns a total amount which is a sum of held and available funds.
-    pub fn total(&self) -> Decimal {
-        self.available + self.held
+    #[test]
+    fn float_amounts() -> TestResult {
+        let mut a = AmountsOf::<f64>::default();
+        a.deposit(2.5)?;
+        assert_eq!(a.withdraw(3.0), Err(AmountsError::InsufficientFunds));
+        a.try_hold(1.5)?;
+        assert_eq!((a.available, a.held), (1.0, 1.5));
+        a.chargeback(1.5)?;
+        assert_eq!(a.total(), 1.0);
+        assert_eq!(a.deposit(f64::INFINITY), Err(AmountsError::Overflow));
+        assert_eq!(a.available, 1.0);
+        Ok(())
     }
-}
 
-/// Process the input CSV file.
-///
-/// The input file will have the values stripped of whitespace.
-pub fn process(file: impl AsRef<Path>) -> Result<HashMap<ClientId, Account>> {
-    let mut rdr = csv::ReaderBuilder::new().trim(Trim::All).from_path(file)?;
-
-    let mut accounts = HashMap::<ClientId, Account>::new();
-    let mut txns = HashMap::<TxId, Decimal>::new();
-    let mut disputed: HashSet<TxId> = HashSet::new();
-    for record in rdr.deserialize() {
-        let record: Record = record?;
-        let account = accounts.entry(record.client).or_insert_with(|| Account {
-            client: record.client,
+    /// Fixed-point amount counting hundredths.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+    struct Cents(i64);
+
+    impl Amount for Cents {
+        const ZERO: Self = Cents(0);
+
+        fn from_decimal(amount: Decimal) -> Option<Self> {
+            use rust_decimal::prelude::ToPrimitive;
+
+            let cents = amount.checked_mul(100.into())?;
+            // fractions of cents cannot be represented
+            cents.fract().is_zero().then_some(())?;
+            cents.to_i64().map(Cents)
+        }
+
+        fn checked_add(self, other: Self) -> Option<Self> {
+            self.0.checked_add(other.0).map(Cents)
+        }
+
+        fn checked_sub(self, other: Self) -> Option<Self> {
+            self.0.checked_sub(other.0).map(Cents)
+        }
+    }
+
+    #[test]
+    fn fixed_point_amounts() -> TestResult {
+        let mut a = AmountsOf {
+            available: Cents(100),
+            held: Cents(0),
+        };
+        assert_eq!(a.hold_clamped(Cents(150))?, Cents(100));
+        assert!(a.is_solvent());
+        a.release(Cents(100))?;
+        assert_eq!(a.deposit(Cents(i64::MAX)), Err(AmountsError::Overflow));
+        assert_eq!(a.checked_total(), Some(Cents(100)));
+        Ok(())
+    }
+
+    #[test]
+    fn fixed_point_engine() -> TestResult {
+        let mut engine = Engine::<Cents>::default();
+        engine.process_file("tests/test-cases/dispute-withdrawal-chargeback.input.csv")?;
+        engine.apply(record(RecordType::Deposit, 1, 3, Some("0.001".parse()?)))?;
+        assert_eq!(engine.accounts()[&1].amounts.available, Cents(600));
+        assert_eq!(
+            engine.transaction(2).map(StoredTx::amount),
+            Some(Cents(400))
+        );
+        // the fraction of a cent is rejected instead of being rounded
+        let rejection = engine.rejections().last().ok_or("deposit not rejected")?;
+        assert_eq!(
+            (rejection.tx, rejection.reason),
+            (3, RejectionReason::Overflow)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn release_insufficient_held() -> TestResult {
+        let mut a = Amounts {
+            available: 1.into(),
+            held: 2.into(),
+        };
+        assert_eq!(a.release(3.into()), Err(AmountsError::InsufficientHeld));
+        assert_eq!(a.available, 1.into());
+        assert_eq!(a.held, 2.into());
+
+        // releasing exactly the held amount is fine
+        a.release(2.into())?;
+        assert_eq!(a.available, 3.into());
+        assert_eq!(a.held, 0.into());
+        Ok(())
+    }
+
+    #[test]
+    fn chargeback_insufficient_held() -> TestResult {
+        let mut a = Amounts {
+            available: 1.into(),
+            held: 2.into(),
+        };
+        assert_eq!(a.chargeback(3.into()), Err(AmountsError::InsufficientHeld));
+        assert_eq!(a.available, 1.into());
+        assert_eq!(a.held, 2.into());
+
+        a.chargeback(2.into())?;
+        assert_eq!(a.available, 1.into());
+        assert_eq!(a.held, 0.into());
+        Ok(())
+    }
+
+    #[test]
+    fn amounts_overflow() {
+        let max = Amounts {
+            available: Decimal::MAX,
+            held: Decimal::MAX,
+        };
+
+        let mut a = max.clone();
+        assert_eq!(a.deposit(1.into()), Err(AmountsError::Overflow));
+        assert_eq!(a.hold_reversal(1.into()), Err(AmountsError::Overflow));
+        assert_eq!(a.release(Decimal::MIN), Err(AmountsError::Overflow));
+        assert_eq!(a, max);
+
+        let mut a = Amounts {
+            available: Decimal::MIN,
+            held: Decimal::ZERO,
+        };
+        assert_eq!(a.hold(1.into()), Err(AmountsError::Overflow));
+        assert_eq!(a.available, Decimal::MIN);
+        assert_eq!(a.held, Decimal::ZERO);
+
+        let mut a = max.clone();
+        assert_eq!(a.chargeback((-1).into()), Err(AmountsError::Overflow));
+        assert_eq!(a, max);
+
+        // held can be decreased but available overflows
+        let mut a = Amounts {
+            available: Decimal::MAX,
+            held: 5.into(),
+        };
+        assert_eq!(a.release(1.into()), Err(AmountsError::Overflow));
+        assert_eq!(a.available, Decimal::MAX);
+        assert_eq!(a.held, 5.into());
+    }
+
+    #[test]
+    fn parse_exact_amounts() -> Result<()> {
+        let accounts = process_str(
+            "type,client,tx,amount\n\
+             deposit,1,1,79228162514264337593543950335\n\
+             deposit,2,2,0.30000000000000001\n\
+             deposit,3,3,1e2\n",
+        )?;
+        assert_eq!(accounts[&1].amounts.available, Decimal::MAX);
+        // rounded to four decimal places
+        assert_eq!(accounts[&2].amounts.available, Decimal::new(3, 1));
+        assert_eq!(accounts[&3].amounts.available, 100.into());
+
+        let accounts = process_reader_with(
+            &b"type,client,tx,amount\ndeposit,1,1,0.30000000000000001\n"[..],
+            &ProcessOptions::new()
+                .strict(true)
+                .excess_precision(ExcessPrecisionPolicy::Reject),
+        );
+        assert!(matches!(
+            accounts,
+            Err(Error::AtRow { row: 1, source, .. }) if matches!(*source, Error::TooPrecise(1))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn engine_with_config() -> Result<()> {
+        let config = EngineConfig {
+            strict: true,
             ..Default::default()
-        });
-        match record.kind {
-            RecordType::Deposit => {
-                let Some(amount) = record.amount else {
-                    return Err(Error::DepositNoAmount(record.tx));
-                };
-                account.amounts.deposit(amount);
-                txns.entry(record.tx).or_insert(amount);
+        };
+        assert_eq!(
+            ProcessOptions::from(config.clone()).config(),
+            ProcessOptions::new().strict(true).config()
+        );
+
+        let mut engine = Engine::with_config(config);
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(1.into())))?;
+        assert!(matches!(
+            engine.apply(record(RecordType::Withdrawal, 1, 2, Some(2.into()))),
+            Err(Error::InsufficientFunds(2))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn checked_operations_overflow() {
+        let max = Amounts {
+            available: Decimal::MAX,
+            held: Decimal::MAX,
+        };
+
+        // negative amounts pass the funds checks but still overflow
+        let mut a = max.clone();
+        assert_eq!(a.withdraw((-1).into()), Err(AmountsError::Overflow));
+        assert_eq!(a.try_hold(1.into()), Err(AmountsError::Overflow));
+        assert_eq!(a.hold_clamped(1.into()), Err(AmountsError::Overflow));
+        assert_eq!(a, max);
+    }
+
+    #[test]
+    fn engine_overflow() -> Result<()> {
+        let mut engine = Engine::new();
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(Decimal::MAX)))?;
+        // the second deposit is ignored instead of panicking
+        engine.apply(record(RecordType::Deposit, 1, 2, Some(Decimal::MAX)))?;
+        assert_eq!(engine.accounts()[&1].amounts.available, Decimal::MAX);
+
+        // and cannot be disputed since it has not been applied
+        engine.apply(record(RecordType::Dispute, 1, 2, None))?;
+        assert_eq!(engine.accounts()[&1].amounts.held, Decimal::ZERO);
+
+        let mut engine = Engine::with_options(ProcessOptions::new().strict(true));
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(Decimal::MAX)))?;
+        assert!(matches!(
+            engine.apply(record(RecordType::Deposit, 1, 2, Some(Decimal::ONE))),
+            Err(Error::Overflow(2))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn clamped_dispute_releases_held_amount() -> Result<()> {
+        let options = ProcessOptions::new().dispute_overdraft(DisputeOverdraftPolicy::ClampToZero);
+        let mut engine = Engine::with_options(options);
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(10.into())))?;
+        engine.apply(record(RecordType::Withdrawal, 1, 2, Some(6.into())))?;
+        engine.apply(record(RecordType::Dispute, 1, 1, None))?;
+        assert_eq!(engine.accounts()[&1].amounts.held, 4.into());
+        engine.apply(record(RecordType::Resolve, 1, 1, None))?;
+
+        let amounts = &engine.accounts()[&1].amounts;
+        assert_eq!(amounts.available, 4.into());
+        assert_eq!(amounts.held, 0.into());
+        Ok(())
+    }
+
+    #[test]
+    fn checked_total_overflow() {
+        let a = Amounts {
+            available: Decimal::MAX,
+            held: 1.into(),
+        };
+        assert_eq!(a.checked_total(), None);
+
+        let a = Amounts {
+            available: Decimal::MAX - Decimal::ONE,
+            held: 1.into(),
+        };
+        assert_eq!(a.checked_total(), Some(Decimal::MAX));
+    }
+
+    #[test]
+    fn validate_invariants() {
+        let mut account = Account {
+            client: 1,
+            ..Default::default()
+        };
+        assert!(account.validate().is_ok());
+
+        account.amounts.held = (-1).into();
+        assert!(matches!(
+            account.validate(),
+            Err(Error::Invariant { client: 1, .. })
+        ));
+
+        account.amounts = Amounts {
+            available: Decimal::MAX,
+            held: 1.into(),
+        };
+        assert!(matches!(
+            account.validate(),
+            Err(Error::Invariant { client: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn serialize_overflow() {
+        let account = Account {
+            client: 1,
+            amounts: Amounts {
+                available: Decimal::MAX,
+                held: Decimal::MAX,
+            },
+            ..Default::default()
+        };
+        let mut writer = csv::Writer::from_writer(vec![]);
+        assert!(writer.serialize(&account).is_err());
+    }
+
+    #[test]
+    fn serialize_rounded() -> TestResult {
+        let mut account = Account::default();
+        account.amounts.deposit(Decimal::new(100005, 5))?;
+        account.amounts.deposit(Decimal::new(100001, 5))?;
+        account.amounts.hold(Decimal::new(5, 5))?;
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.serialize(&account)?;
+        writer.serialize(account.rounded(Precision {
+            decimal_places: 4,
+            strategy: RoundingStrategy::MidpointAwayFromZero,
+        }))?;
+        assert_eq!(
+            String::from_utf8_lossy(&writer.into_inner().expect("flushing to succeed")),
+            "client,available,held,total,locked\n\
+             0,2.0000,0.0000,2.0001,false\n\
+             0,2.0000,0.0001,2.0001,false\n"
+        );
+
+        // internal state retains full precision
+        assert_eq!(account.amounts.held, Decimal::new(5, 5));
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::padded("1.5", 4, "1.5000")]
+    #[case::integer("2", 4, "2.0000")]
+    #[case::rounded_to_zero("0.00005", 4, "0.0000")]
+    #[case::rounded_up("0.00015", 4, "0.0002")]
+    #[case::negative("-1.5", 4, "-1.5000")]
+    #[case::negative_rounded_to_zero("-0.00001", 4, "0.0000")]
+    #[case::two_places("1.005", 2, "1.00")]
+    #[case::no_places("2.5", 0, "2")]
+    fn serialize_padded(
+        #[case] available: &str,
+        #[case] decimal_places: u32,
+        #[case] expected: &str,
+    ) -> TestResult {
+        let mut account = Account::default();
+        account.amounts.available = available.parse()?;
+
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(vec![]);
+        writer.serialize(account.rounded(Precision {
+            decimal_places,
+            ..Default::default()
+        }))?;
+        let output = String::from_utf8(writer.into_inner().expect("flushing to succeed"))?;
+        let fields = output.trim_end().split(',').collect::<Vec<_>>();
+        assert_eq!(fields[1], expected);
+        assert_eq!(fields[3], expected);
+        Ok(())
+    }
+
+    fn record(kind: RecordType, client: ClientId, tx: TxId, amount: Option<Decimal>) -> Record {
+        Record {
+            kind,
+            client,
+            tx,
+            amount,
+        }
+    }
+
+    #[test]
+    fn engine_apply() -> Result<()> {
+        let mut engine = Engine::new();
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(3.into())))?;
+        assert_eq!(engine.accounts()[&1].amounts.available, 3.into());
+
+        engine.apply(record(RecordType::Withdrawal, 1, 2, Some(1.into())))?;
+        assert_eq!(engine.accounts()[&1].amounts.available, 2.into());
+        Ok(())
+    }
+
+    #[test]
+    fn engine_interleaved_matches_process() -> Result<()> {
+        let mut engine = Engine::new();
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(5.into())))?;
+        engine.apply(record(RecordType::Deposit, 2, 2, Some(3.into())))?;
+        engine.apply(record(RecordType::Dispute, 1, 1, None))?;
+
+        let account = &engine.accounts()[&1];
+        assert_eq!(account.amounts.available, 0.into());
+        assert_eq!(account.amounts.held, 5.into());
+
+        engine.apply(record(RecordType::Deposit, 1, 3, Some(2.into())))?;
+        engine.apply(record(RecordType::Dispute, 2, 2, None))?;
+        engine.apply(record(RecordType::Chargeback, 1, 1, None))?;
+        engine.apply(record(RecordType::Resolve, 2, 2, None))?;
+
+        let account = &engine.accounts()[&1];
+        assert_eq!(account.amounts.available, 2.into());
+        assert_eq!(account.amounts.held, 0.into());
+        assert!(account.locked);
+
+        let expected = process_str(
+            "type,client,tx,amount
+deposit,1,1,5
+deposit,2,2,3
+dispute,1,1,
+deposit,1,3,2
+dispute,2,2,
+chargeback,1,1,
+resolve,2,2,
+",
+        )?;
+        assert_eq!(engine.into_accounts(), expected);
+        Ok(())
+    }
+
+    fn interleaved_records() -> Vec<Record> {
+        vec![
+            record(RecordType::Deposit, 1, 1, Some(5.into())),
+            record(RecordType::Deposit, 2, 2, Some(3.into())),
+            record(RecordType::Dispute, 1, 1, None),
+            record(RecordType::Withdrawal, 2, 3, Some(1.into())),
+            record(RecordType::Chargeback, 1, 1, None),
+        ]
+    }
+
+    #[test]
+    fn engine_collect() -> Result<()> {
+        let engine: Engine = interleaved_records().into_iter().collect();
+        assert_eq!(
+            engine.into_accounts(),
+            process_records(interleaved_records())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn engine_extend_skips_errors() -> Result<()> {
+        let mut engine = Engine::new();
+        engine.extend(vec![
+            record(RecordType::Deposit, 1, 1, Some(5.into())),
+            record(RecordType::Deposit, 1, 2, None),
+        ]);
+        engine.extend(vec![record(RecordType::Withdrawal, 1, 3, Some(2.into()))]);
+
+        assert_eq!(engine.accounts()[&1].amounts.available, 3.into());
+        assert!(process_records(vec![record(RecordType::Deposit, 1, 2, None)]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_without_dispute() -> Result<()> {
+        let mut engine = Engine::new();
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(5.into())))?;
+        engine.apply(record(RecordType::Resolve, 1, 1, None))?;
+
+        let amounts = &engine.accounts()[&1].amounts;
+        assert_eq!(amounts.available, 5.into());
+        assert_eq!(amounts.held, 0.into());
+        Ok(())
+    }
+
+    #[test]
+    fn chargeback_withdrawal_refunds() -> Result<()> {
+        let options = ProcessOptions::new().withdrawal_disputes(WithdrawalDisputePolicy::Reverse);
+        let mut engine = Engine::with_options(options);
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(10.into())))?;
+        engine.apply(record(RecordType::Withdrawal, 1, 2, Some(4.into())))?;
+        engine.apply(record(RecordType::Dispute, 1, 2, None))?;
+        engine.apply(record(RecordType::Chargeback, 1, 2, None))?;
+
+        let account = &engine.accounts()[&1];
+        assert_eq!(account.amounts.available, 10.into());
+        assert_eq!(account.amounts.held, 0.into());
+        assert!(account.locked);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_counts() -> Result<()> {
+        let (_, summaries) = process_with_summary("tests/test-cases/chargeback-ok.input.csv")?;
+        assert_eq!(
+            summaries[&1],
+            Summary {
+                deposits: 1,
+                disputes: 1,
+                chargebacks: 1,
+                ..Default::default()
             }
-            RecordType::Withdrawal => {
-                let Some(amount) = record.amount else {
-                    return Err(Error::WithdrawNoAmount(record.tx));
-                };
-                if account.amounts.withdraw(amount) {
-                    txns.entry(record.tx).or_insert(amount);
-                } else {
-                    log::info!("Transaction {} failed - insufficient funds.", record.tx);
-                }
+        );
+
+        let mut engine = Engine::new();
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(5.into())))?;
+        engine.apply(record(RecordType::Withdrawal, 1, 2, Some(2.into())))?;
+        // rejected records are not counted
+        engine.apply(record(RecordType::Withdrawal, 1, 3, Some(9.into())))?;
+        engine.apply(record(RecordType::Resolve, 1, 1, None))?;
+        engine.apply(record(RecordType::Deposit, 1, 4, Some(1.into())))?;
+        engine.apply(record(RecordType::Dispute, 1, 4, None))?;
+        engine.apply(record(RecordType::Resolve, 1, 4, None))?;
+        assert_eq!(
+            engine.summaries()[&1],
+            Summary {
+                deposits: 2,
+                withdrawals: 1,
+                disputes: 1,
+                resolves: 1,
+                chargebacks: 0,
             }
-            RecordType::Dispute => {
-                if let Some(amount) = txns.get(&record.tx) {
-                    account.amounts.hold(*amount);
-                    disputed.insert(record.tx);
-                } else {
-                    log::info!("Dispute failed - transaction {} not found.", record.tx);
-                }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn account_counters() -> Result<()> {
+        let mut engine = Engine::new();
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(5.into())))?;
+        engine.apply(record(RecordType::Deposit, 1, 2, Some(1.into())))?;
+        engine.apply(record(RecordType::Dispute, 1, 1, None))?;
+        engine.apply(record(RecordType::Resolve, 1, 1, None))?;
+        engine.apply(record(RecordType::Dispute, 1, 2, None))?;
+        engine.apply(record(RecordType::Chargeback, 1, 2, None))?;
+        // rejected records are not counted
+        engine.apply(record(RecordType::Chargeback, 1, 1, None))?;
+
+        let account = &engine.accounts()[&1];
+        assert_eq!(
+            (
+                account.disputes_opened,
+                account.disputes_resolved,
+                account.chargebacks,
+                account.locked_by
+            ),
+            (2, 1, 1, Some(2))
+        );
+        // the counters are not compared
+        let expected = Account {
+            client: 1,
+            amounts: Amounts {
+                available: 5.into(),
+                held: 0.into(),
+            },
+            locked: true,
+            ..Default::default()
+        };
+        assert_eq!(*account, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn illegal_transitions() -> Result<()> {
+        let strict = ProcessOptions::new().strict(true);
+        let mut engine = Engine::with_options(strict);
+        engine.apply(record(RecordType::Deposit, 1, 1, Some(5.into())))?;
+        engine.apply(record(RecordType::Deposit, 1, 2, Some(5.into())))?;
+
+        // open
+        assert!(matches!(
+            engine.apply(record(RecordType::Resolve, 1, 1, None)),
+            Err(Error::NotDisputed(1))
+        ));
+        assert!(matches!(
+            engine.apply(record(RecordType::Chargeback, 1, 1, None)),
+            Err(Error::NotDisputed(1))
+        ));
+
+        // disputed
+        engine.apply(record(RecordType::Dispute, 1, 1, None))?;
+        assert!(matches!(
+            engine.apply(record(RecordType::Dispute, 1, 1, None)),
+            Err(Error::AlreadyDisputed(1))
+        ));
+
+        // resolved
+        engine.apply(record(RecordType::Resolve, 1, 1, None))?;
+        assert!(matches!(
+            engine.apply(record(RecordType::Resolve, 1, 1, None)),
+            Err(Error::NotDisputed(1))
+        ));
+        assert!(matches!(
+            engine.apply(record(RecordType::Chargeback, 1, 1, None)),
+            Err(Error::NotDisputed(1))
+        ));
+
+        // resolved disputes can be reopened
+        engine.apply(record(RecordType::Dispute, 1, 1, None))?;
+        engine.apply(record(RecordType::Resolve, 1, 1, None))?;
+
+        // charged back
+        engine.apply(record(RecordType::Dispute, 1, 2, None))?;
+        engine.apply(record(RecordType::Chargeback, 1, 2, None))?;
+        for kind in [
+            RecordType::Dispute,
+            RecordType::Resolve,
+            RecordType::Chargeback,
+        ] {
+            assert!(matches!(
+                engine.apply(record(kind, 1, 2, None)),
+                Err(Error::ChargedBack(2))
+            ));
+        }
+
+        // none of the rejected records changed the account
+        let account = &engine.accounts()[&1];
+        assert_eq!(account.amounts.available, 5.into());
+        assert_eq!(account.amounts.held, 0.into());
+        assert!(account.locked);
+        Ok(())
+    }
+
+    #[test]
+    fn negative_amounts() -> Result<()> {
+        let strict = ProcessOptions::new().strict(true);
+        let result = process_reader_with(
+            &b"type, client, tx, amount\ndeposit, 1, 1, -5.0\n"[..],
+            &strict,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::AtRow { row: 1, source, .. }) if matches!(
+                *source,
+                Error::NegativeAmount { tx: 1, amount } if amount == Decimal::new(-50, 1)
+            )
+        ));
+
+        let input = "type,client,tx,amount\ndeposit,1,1,5\nwithdrawal,1,2,-1\n";
+        let result = process_reader_with(input.as_bytes(), &strict);
+        assert!(matches!(
+            result,
+            Err(Error::AtRow { row: 2, source, .. })
+                if matches!(*source, Error::NegativeAmount { tx: 2, .. })
+        ));
+
+        // by default negative amounts are ignored
+        let accounts = process_str(input)?;
+        assert_eq!(accounts[&1].amounts.available, 5.into());
+        Ok(())
+    }
+
+    #[test]
+    fn zero_amounts() -> Result<()> {
+        let accounts = process_str("type,client,tx,amount\ndeposit,1,1,0\nwithdrawal,1,2,0.0\n")?;
+        assert_eq!(accounts[&1].amounts.available, 0.into());
+        Ok(())
+    }
+
+    #[test]
+    fn process_reader_cursor() -> Result<()> {
+        let input = b"type,client,tx,amount\ndeposit,1,1,2.0\nwithdrawal,1,2,0.5\n".to_vec();
+        let accounts = process_reader(Cursor::new(input))?;
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[&1].amounts.available, Decimal::new(15, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn process_reader_slice() -> Result<()> {
+        let input: &[u8] = b"type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, 2, 2, 3.0\n";
+        let accounts = process_reader(input)?;
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[&1].amounts.available, 1.into());
+        assert_eq!(accounts[&2].amounts.available, 3.into());
+        Ok(())
+    }
+
+    #[test]
+    fn process_str_extra_trailing_fields() -> Result<()> {
+        let accounts = process_str(
+            "type,client,tx,amount\ndeposit,1,1,2.0,2024-05-01\ndeposit,1,2,1.0\ndispute,1,1,\n",
+        )?;
+        let amounts = &accounts[&1].amounts;
+        assert_eq!(amounts.available, 1.into());
+        assert_eq!(amounts.held, 2.into());
+        Ok(())
+    }
+
+    #[test]
+    fn header_misnamed_column() {
+        let result = process_str("type,client,transaction,amount\ndeposit,1,1,1.0\n");
+        assert!(matches!(result, Err(Error::MissingColumns(columns)) if columns == ["tx"]));
+    }
+
+    #[test]
+    fn header_missing_amount() {
+        let result = process_str("type,client,tx\ndeposit,1,1\n");
+        assert!(matches!(result, Err(Error::MissingColumns(columns)) if columns == ["amount"]));
+    }
+
+    #[test]
+    fn header_extra_column() -> Result<()> {
+        let input = "type,client,tx,amount,timestamp\ndeposit,1,1,1.0,1715000000\n";
+        assert_eq!(process_str(input)?[&1].amounts.available, 1.into());
+
+        let result = process_reader_with(
+            input.as_bytes(),
+            &ProcessOptions::new().reject_unknown_columns(true),
+        );
+        assert!(matches!(result, Err(Error::UnknownColumns(columns)) if columns == ["timestamp"]));
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::lowercase("deposit", RecordType::Deposit)]
+    #[case::capitalized("Deposit", RecordType::Deposit)]
+    #[case::uppercase("DEPOSIT", RecordType::Deposit)]
+    #[case::withdrawal("Withdrawal", RecordType::Withdrawal)]
+    #[case::withdraw("withdraw", RecordType::Withdrawal)]
+    #[case::dispute("DISPUTE", RecordType::Dispute)]
+    #[case::resolve("Resolve", RecordType::Resolve)]
+    #[case::chargeback("ChargeBack", RecordType::Chargeback)]
+    #[case::charge_dash_back("charge-back", RecordType::Chargeback)]
+    #[case::charge_space_back("Charge Back", RecordType::Chargeback)]
+    fn record_type_spellings(#[case] value: &str, #[case] kind: RecordType) -> Result<()> {
+        let input = format!("type,client,tx,amount\n{value},1,1,\n");
+        let record: Record = csv::Reader::from_reader(input.as_bytes())
+            .deserialize()
+            .next()
+            .expect("record to be present")?;
+        assert_eq!(record.kind, kind);
+        Ok(())
+    }
+
+    #[test]
+    fn record_type_unknown() {
+        let result = process_str("type,client,tx,amount\nchargebacks,1,1,\n");
+        assert!(matches!(result, Err(Error::Parse { line: 2, .. })));
+    }
+
+    #[test]
+    fn error_code_tx_id_and_client() {
+        let error = Error::AtRow {
+            row: 2,
+            byte: None,
+            source: Box::new(Error::DepositNoAmount(7)),
+        };
+        assert_eq!(error.code(), "deposit_no_amount");
+        assert_eq!(error.tx_id(), Some(7));
+        assert_eq!(error.client(), None);
+        assert_eq!(
+            error.to_string(),
+            "Record 2: Deposit used but no amount is specified in transaction 7"
+        );
+
+        let error = Error::ClientMismatch {
+            tx: 3,
+            expected: 1,
+            got: 2,
+        };
+        assert_eq!(error.code(), "client_mismatch");
+        assert_eq!(error.tx_id(), Some(3));
+        assert_eq!(error.client(), Some(2));
+
+        let error = Error::AtRow {
+            row: 4,
+            byte: None,
+            source: Box::new(Error::AccountLocked { client: 5, tx: 6 }),
+        };
+        assert_eq!(error.client(), Some(5));
+
+        let error = Error::MissingColumns(vec!["tx".into()]);
+        assert_eq!(error.code(), "missing_columns");
+        assert_eq!(error.tx_id(), None);
+    }
+
+    #[test]
+    fn error_codes_match_rejection_reasons() -> TestResult {
+        for error in [
+            Error::InsufficientFunds(1),
+            Error::InsufficientHeld(1),
+            Error::Overflow(1),
+            Error::TxNotFound(1),
+            Error::NotDisputable(1),
+            Error::AlreadyDisputed(1),
+            Error::NotDisputed(1),
+            Error::ChargedBack(1),
+            Error::DuplicateTx(1),
+            Error::TooPrecise(1),
+        ] {
+            let reason = RejectionReason::of(&error).expect("error to be a rejection");
+            assert_eq!(serde_json::to_value(reason)?, error.code());
+            assert_eq!(reason.code(), error.code());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn record_type_from_str() -> TestResult {
+        for kind in [
+            RecordType::Deposit,
+            RecordType::Withdrawal,
+            RecordType::Dispute,
+            RecordType::Resolve,
+            RecordType::Chargeback,
+        ] {
+            assert_eq!(kind.to_string().parse::<RecordType>()?, kind);
+        }
+        assert_eq!("Charge Back".parse::<RecordType>()?, RecordType::Chargeback);
+
+        let error = "transfer"
+            .parse::<RecordType>()
+            .expect_err("type to be unknown");
+        assert_eq!(error.value(), "transfer");
+        assert_eq!(error.to_string(), "Unknown record type \"transfer\"");
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::deposit_no_amount(RecordType::Deposit, None)]
+    #[case::withdrawal_no_amount(RecordType::Withdrawal, None)]
+    #[case::negative_deposit(RecordType::Deposit, Some(-1))]
+    #[case::negative_withdrawal(RecordType::Withdrawal, Some(-1))]
+    #[case::dispute_amount(RecordType::Dispute, Some(1))]
+    #[case::resolve_amount(RecordType::Resolve, Some(1))]
+    #[case::chargeback_amount(RecordType::Chargeback, Some(1))]
+    fn record_validate_fails(#[case] kind: RecordType, #[case] amount: Option<i64>) {
+        let record = Record {
+            kind,
+            client: 1,
+            tx: 1,
+            amount: amount.map(Decimal::from),
+        };
+        let result = record.validate();
+        match (kind, amount) {
+            (RecordType::Deposit, None) => {
+                assert!(matches!(result, Err(Error::DepositNoAmount(1))))
             }
-            RecordType::Resolve => {
-                if let Some(amount) = txns.get(&record.tx) {
-                    account.amounts.release(*amount);
-                    disputed.remove(&record.tx);
-                } else {
-                    log::info!("Resolve failed - transaction {} not found.", record.tx);
-                }
+            (RecordType::Withdrawal, None) => {
+                assert!(matches!(result, Err(Error::WithdrawNoAmount(1))))
             }
-            RecordType::Chargeback => {
-                if let Some(amount) = txns.get(&record.tx) {
-                    if disputed.contains(&record.tx) {
-                        account.amounts.chargeback(*amount);
-                        // "frozen" means "locked == true"
-                        account.locked = true;
-                        disputed.remove(&record.tx);
-                    } else {
-                        log::info!(
-                            "Chargeback failed - transaction {} not under dispute.",
-                            record.tx
-                        );
-                    }
-                } else {
-                    log::info!("Chargeback failed - transaction {} not found.", record.tx);
-                }
+            (RecordType::Deposit | RecordType::Withdrawal, _) => {
+                assert!(matches!(result, Err(Error::NegativeAmount { tx: 1, .. })))
             }
+            _ => assert!(
+                matches!(result, Err(Error::UnexpectedAmount { tx: 1, kind: got }) if got == kind)
+            ),
         }
     }
-    Ok(accounts)
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[rstest::rstest]
+    #[case::deposit(RecordType::Deposit, Some(1))]
+    #[case::zero_withdrawal(RecordType::Withdrawal, Some(0))]
+    #[case::dispute(RecordType::Dispute, None)]
+    #[case::resolve(RecordType::Resolve, None)]
+    #[case::chargeback(RecordType::Chargeback, None)]
+    fn record_validate_ok(#[case] kind: RecordType, #[case] amount: Option<i64>) -> Result<()> {
+        let record = Record {
+            kind,
+            client: 1,
+            tx: 1,
+            amount: amount.map(Decimal::from),
+        };
+        record.validate()
+    }
 
     #[test]
-    fn withdraw_ok() {
-        let mut a = Amounts::default();
-        a.deposit(2.into());
-        assert!(a.withdraw(1.into()));
-        assert_eq!(a.available, 1.into());
-        assert_eq!(a.held, 0.into());
+    fn process_client_only() -> Result<()> {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,1.0\ndeposit,2,2,2.0\ndeposit,3,3,3.0\n\
+                     deposit,2,4,5.0\ndispute,1,1,\nwithdrawal,2,5,1.5\ndispute,2,2,\n";
+        let account = process_client(input.as_bytes(), 2)?.expect("client 2 to be present");
+        assert_eq!(account, process_str(input)?[&2]);
+        assert_eq!(account.amounts.available, Decimal::new(35, 1));
+        assert_eq!(account.amounts.held, 2.into());
+
+        assert_eq!(process_client(input.as_bytes(), 4)?, None);
+        Ok(())
     }
 
     #[test]
-    fn withdraw_failed() {
-        let mut a = Amounts::default();
-        a.deposit(1.into());
-        assert!(!a.withdraw(2.into()));
-        assert_eq!(a.available, 1.into());
-        assert_eq!(a.held, 0.into());
+    fn unexpected_amount_ignored() -> Result<()> {
+        let with_amounts = process_str(
+            "type,client,tx,amount\n\
+             deposit,1,1,1.0\ndeposit,1,2,2.0\ndeposit,1,3,4.0\n\
+             dispute,1,1,99.0\nresolve,1,1,99.0\n\
+             dispute,1,2,99.0\nchargeback,1,2,99.0\ndispute,1,3,99.0\n",
+        )?;
+        let without_amounts = process_str(
+            "type,client,tx,amount\n\
+             deposit,1,1,1.0\ndeposit,1,2,2.0\ndeposit,1,3,4.0\n\
+             dispute,1,1,\nresolve,1,1,\n\
+             dispute,1,2,\nchargeback,1,2,\ndispute,1,3,\n",
+        )?;
+        assert_eq!(with_amounts, without_amounts);
+
+        // the amounts of the referenced transactions are used
+        let amounts = &with_amounts[&1].amounts;
+        assert_eq!(amounts.available, 1.into());
+        assert_eq!(amounts.held, 4.into());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_error_line() {
+        let result = process_str("type,client,tx,amount\ndeposit,1,1,1.0\ndeposits,1,2,1.0\n");
+        assert!(matches!(
+            result,
+            Err(Error::Parse {
+                line: 3,
+                row: 2,
+                ..
+            })
+        ));
+
+        let result = process_str("type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,x,1.0\n");
+        assert!(matches!(
+            result,
+            Err(Error::InvalidField { row: 2, column: "tx", value }) if value == "x"
+        ));
+
+        // rows are numbered the same way without the header line
+        let options = ProcessOptions::new().has_headers(false);
+        let result =
+            process_reader_with("deposit,1,1,1.0\ndeposits,1,2,1.0\n".as_bytes(), &options);
+        assert!(matches!(
+            result,
+            Err(Error::Parse {
+                line: 2,
+                row: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn process_str_trims_whitespace() -> Result<()> {
+        let spaces = process_str("type, client, tx, amount\n deposit, 1, 1, 1.0\n")?;
+        let plain = process_str("type,client,tx,amount\ndeposit,1,1,1.0\n")?;
+        assert_eq!(spaces, plain);
+        assert_eq!(spaces[&1].amounts.available, 1.into());
+        Ok(())
+    }
+
+    #[test]
+    fn accounts_by_total() -> Result<()> {
+        let accounts = process_str(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\ndeposit,2,2,5\ndeposit,3,3,3\ndispute,2,2,\nwithdrawal,3,4,2\n",
+        )?;
+        let mut accounts = sorted_accounts(accounts);
+        accounts.sort_by(|a, b| b.cmp_by_total(a));
+        let totals = accounts
+            .iter()
+            .map(|account| (account.client, account.total()))
+            .collect::<Vec<_>>();
+        assert_eq!(totals, [(2, 5.into()), (1, 2.into()), (3, 1.into())]);
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_accounts_order() -> Result<()> {
+        let accounts =
+            process_str("type,client,tx,amount\ndeposit,3,1,1\ndeposit,1,2,1\ndeposit,2,3,1\n")?;
+        let clients = sorted_accounts(accounts)
+            .iter()
+            .map(|account| account.client)
+            .collect::<Vec<_>>();
+        assert_eq!(clients, [1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn write_accounts_header() -> TestResult {
+        let mut account = Account {
+            client: 2,
+            ..Default::default()
+        };
+        account.amounts.deposit(Decimal::new(15, 1))?;
+
+        let mut out = vec![];
+        write_accounts(&mut out, [&account])?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "client,available,held,total,locked\n2,1.5000,0.0000,1.5000,false\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write_accounts_delimiter() -> TestResult {
+        let mut account = Account::default();
+        account.amounts.deposit(Decimal::new(15, 1))?;
+
+        let mut out = vec![];
+        write_accounts_with(
+            &mut out,
+            [&account],
+            OutputOptions {
+                delimiter: b'\t',
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "client\tavailable\theld\ttotal\tlocked\n0\t1.5000\t0.0000\t1.5000\tfalse\n"
+        );
+
+        // fields containing the delimiter are quoted
+        let mut out = vec![];
+        write_accounts_with(
+            &mut out,
+            [&account],
+            OutputOptions {
+                delimiter: b'.',
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "client.available.held.total.locked\n0.\"1.5000\".\"0.0000\".\"1.5000\".false\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write_accounts_empty() -> Result<()> {
+        let mut out = vec![];
+        write_accounts(&mut out, [])?;
+        assert_eq!(out, b"client,available,held,total,locked\n");
+        Ok(())
+    }
+
+    #[test]
+    fn process_into_writer_output() -> Result<()> {
+        let mut out = vec![];
+        process_into_writer(
+            &b"type,client,tx,amount\ndeposit,1,1,1.5\ndeposit,1,2,1\n"[..],
+            &mut out,
+        )?;
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            "client,available,held,total,locked\n1,2.5000,0.0000,2.5000,false\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn process_reader_empty() -> Result<()> {
+        let accounts = process_reader(&b""[..])?;
+        assert!(accounts.is_empty());
+        Ok(())
     }
 }
@@ -1,25 +1,367 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
 
-use clap::Parser;
-use csv::Writer;
-use tx_engine::process;
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
+use tx_engine::{
+    sorted_accounts, write_accounts_extended, write_accounts_json, write_accounts_jsonl,
+    write_accounts_with, write_rejections, Account, ClientId, Engine, Error, Event, OutputOptions,
+    Precision, ProcessOptions, Result,
+};
 
 #[derive(Debug, Parser)]
 struct Args {
-    input: PathBuf,
+    /// Input CSV files processed in order. Standard input is used if a
+    /// file is `-` or no file is given.
+    inputs: Vec<PathBuf>,
+
+    /// Format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+
+    /// Check consistency of all accounts before printing them. Accounts
+    /// with negative funds are listed as warnings.
+    #[arg(long)]
+    validate: bool,
+
+    /// Write the accounts to a file instead of the standard output.
+    ///
+    /// The file is replaced only if all accounts have been written.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Write rejected transactions and skipped rows to a CSV file.
+    ///
+    /// The file has the `row`, `type`, `client`, `tx`, `amount`,
+    /// `reason` and `raw` columns.
+    #[arg(long)]
+    rejected_output: Option<PathBuf>,
+
+    /// Print a JSON summary of the processing (numbers of records,
+    /// rejections and accounts, sum of all funds) to the standard error.
+    #[arg(long)]
+    summary: bool,
+
+    /// Add the numbers of disputes, resolves and chargebacks as well as
+    /// the transaction which has locked the account to the CSV output.
+    #[arg(long)]
+    extended_output: bool,
+
+    /// Number of decimal places of the amounts in the output, at most
+    /// four since amounts are stored with four decimal places.
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(0..=4))]
+    precision: u32,
+
+    /// Character separating the fields of the input and the output: `,`,
+    /// `;`, `|` or a tab (given as `\t` or as the character itself).
+    #[arg(long, default_value = ",", value_parser = parse_delimiter)]
+    delimiter: u8,
+
+    /// Inputs have no header line. The columns are `type`, `client`,
+    /// `tx` and `amount`, in this order.
+    #[arg(long)]
+    no_headers: bool,
+
+    /// Write the accounts computed so far even if the processing of
+    /// the input fails. The program still exits with an error.
+    #[arg(long)]
+    lenient: bool,
+
+    /// Format of rejections and errors printed to the standard error.
+    #[arg(long, value_enum, default_value_t = ErrorsFormat::Text)]
+    errors_format: ErrorsFormat,
+}
+
+/// Output format.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    /// CSV with a header line.
+    Csv,
+
+    /// JSON array of account objects.
+    Json,
+
+    /// JSON account objects, one per line.
+    Jsonl,
+}
+
+/// Parses the delimiter given in the command line.
+fn parse_delimiter(value: &str) -> std::result::Result<u8, String> {
+    match value {
+        "," => Ok(b','),
+        ";" => Ok(b';'),
+        "|" => Ok(b'|'),
+        "\t" | "\\t" => Ok(b'\t'),
+        _ => Err(format!("unsupported delimiter {value:?}")),
+    }
+}
+
+/// Format of rejections and errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorsFormat {
+    /// Errors are printed as text, rejections are logged.
+    Text,
+
+    /// One JSON object per line with `row`, `tx`, `client`, `reason`
+    /// and `message` fields.
+    Json,
+}
+
+/// Exit code used for errors not caused by the input, e.g. a snapshot
+/// which cannot be read or has an unsupported version.
+const EXIT_FAILURE: i32 = 1;
+
+/// Exit code used when the input file cannot be read. Invalid
+/// command-line arguments use the same code.
+const EXIT_USAGE: i32 = 2;
+
+/// Exit code used when the input is not a valid CSV file of records.
+const EXIT_PARSE: i32 = 3;
+
+/// Exit code used when a record fails in strict mode or an account is
+/// inconsistent.
+const EXIT_VALIDATION: i32 = 4;
+
+/// Returns the process exit code for an error aborting the processing.
+fn exit_code_for(error: &Error) -> i32 {
+    match error {
+        Error::InFile { source, .. } | Error::AtRow { source, .. } => exit_code_for(source),
+        Error::Io(_) => EXIT_USAGE,
+        Error::Parse { .. }
+        | Error::InvalidField { .. }
+        | Error::MissingColumns(_)
+        | Error::UnknownColumns(_)
+        | Error::Csv(_)
+        | Error::DepositNoAmount(_)
+        | Error::WithdrawNoAmount(_) => EXIT_PARSE,
+        Error::Json(_) | Error::SnapshotVersion => EXIT_FAILURE,
+        _ => EXIT_VALIDATION,
+    }
 }
 
-fn main() -> testresult::TestResult {
+fn main() {
     env_logger::init();
 
     let args = Args::parse();
+    if args.extended_output && !matches!(args.format, Format::Csv) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--extended-output is supported only with the CSV format",
+            )
+            .exit();
+    }
+    let errors_format = args.errors_format;
+    if let Err(error) = run(args) {
+        match errors_format {
+            ErrorsFormat::Text => eprintln!("Error: {error}"),
+            ErrorsFormat::Json => eprintln!(
+                "{}",
+                serde_json::json!({
+                    "row": error.row(),
+                    "tx": error.tx_id(),
+                    "client": error.client(),
+                    "reason": error.code(),
+                    "message": error.to_string(),
+                })
+            ),
+        }
+        std::process::exit(exit_code_for(&error));
+    }
+}
 
-    let output = process(args.input)?;
+fn run(args: Args) -> Result<()> {
+    // unknown record types are skipped so that new types emitted by
+    // upstream systems do not abort the whole run
+    let mut engine = Engine::with_options(
+        ProcessOptions::new()
+            .skip_unknown_types(true)
+            .delimiter(args.delimiter)
+            .has_headers(!args.no_headers),
+    );
+    if args.errors_format == ErrorsFormat::Json {
+        engine.set_event_sink(|event| {
+            if let Event::Rejected(rejection) = event {
+                let mut line = serde_json::json!(rejection);
+                line["message"] = rejection.to_string().into();
+                eprintln!("{line}");
+            }
+        });
+    }
+    let processed = process_inputs(&mut engine, &args.inputs);
+    if processed.is_err() && !args.lenient {
+        return processed;
+    }
+
+    let report = engine.report();
+    if args.summary {
+        eprintln!("{}", serde_json::to_string(&report)?);
+    }
+    if let Some(path) = &args.rejected_output {
+        write_atomically(path, |out| write_rejections(out, &report))?;
+    }
+    let accounts = engine.into_accounts();
+    if args.validate {
+        accounts.values().try_for_each(Account::validate)?;
+        let mut insolvent = accounts
+            .values()
+            .filter(|account| !account.is_solvent())
+            .map(|account| account.client)
+            .collect::<Vec<_>>();
+        if !insolvent.is_empty() {
+            insolvent.sort_unstable();
+            let clients = insolvent
+                .iter()
+                .map(ClientId::to_string)
+                .collect::<Vec<_>>();
+            eprintln!("Warning: insolvent accounts: {}", clients.join(", "));
+        }
+    }
+    let options = OutputOptions {
+        precision: Precision {
+            decimal_places: args.precision,
+            ..Default::default()
+        },
+        delimiter: args.delimiter,
+    };
+    let extended = args.extended_output;
+    let write = |out: &mut dyn Write| write_accounts(out, args.format, options, accounts, extended);
+    let written = match &args.output {
+        Some(path) => write_atomically(path, |out| write(out)),
+        None => write(&mut std::io::stdout().lock()),
+    };
+    // in lenient mode the accounts are written before reporting the
+    // error of the processing
+    processed.and(written)
+}
 
-    let mut writer = Writer::from_writer(std::io::stdout());
-    for record in output.into_values() {
-        writer.serialize(&record)?;
+/// Applies records of all inputs in order.
+///
+/// Standard input is used if an input is `-` or no input is given.
+fn process_inputs(engine: &mut Engine, inputs: &[PathBuf]) -> Result<()> {
+    if inputs.is_empty() {
+        engine.process_reader(std::io::stdin())?;
+    }
+    for input in inputs {
+        if input.as_os_str() == "-" {
+            engine.process_reader(std::io::stdin())?;
+        } else {
+            engine.process_file(input)?;
+        }
     }
-    writer.flush()?;
     Ok(())
 }
+
+/// Writes the accounts in given format.
+///
+/// The dispute counters are written only if `extended` is set, see
+/// [`write_accounts_extended`].
+fn write_accounts(
+    mut out: impl Write,
+    format: Format,
+    options: OutputOptions,
+    accounts: HashMap<ClientId, Account>,
+    extended: bool,
+) -> Result<()> {
+    let accounts = sorted_accounts(accounts);
+    match format {
+        Format::Csv if extended => write_accounts_extended(&mut out, &accounts, options)?,
+        Format::Csv => write_accounts_with(&mut out, &accounts, options)?,
+        Format::Json => write_accounts_json(&mut out, &accounts, options.precision)?,
+        Format::Jsonl => write_accounts_jsonl(&mut out, &accounts, options.precision)?,
+    }
+    out.flush().map_err(Error::from)
+}
+
+/// Writes the file so that it is either fully written or not changed at
+/// all.
+///
+/// The data is written to a temporary file in the same directory which
+/// is renamed to the target path only if writing succeeds. Otherwise
+/// the temporary file is removed.
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<()>,
+) -> Result<()> {
+    let Some(name) = path.file_name() else {
+        return Err(
+            io::Error::new(io::ErrorKind::InvalidInput, "output path is not a file").into(),
+        );
+    };
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let result = File::create(&temp).map_err(Error::from).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        write(&mut out)?;
+        out.into_inner()
+            .map_err(io::IntoInnerError::into_error)?
+            .sync_all()?;
+        Ok(fs::rename(&temp, path)?)
+    });
+    if result.is_err() {
+        // the file may not have been created at all
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let missing = Error::Io(std::io::ErrorKind::NotFound.into());
+        assert_eq!(exit_code_for(&missing), EXIT_USAGE);
+
+        let in_file = Error::InFile {
+            path: PathBuf::from("missing.csv"),
+            source: Box::new(missing),
+        };
+        assert_eq!(exit_code_for(&in_file), EXIT_USAGE);
+
+        let invalid = Error::InvalidField {
+            row: 1,
+            column: "amount",
+            value: "1.0O".into(),
+        };
+        assert_eq!(exit_code_for(&invalid), EXIT_PARSE);
+        assert_eq!(
+            exit_code_for(&Error::MissingColumns(vec!["tx".into()])),
+            EXIT_PARSE
+        );
+
+        let no_amount = Error::AtRow {
+            row: 1,
+            byte: None,
+            source: Box::new(Error::DepositNoAmount(1)),
+        };
+        assert_eq!(exit_code_for(&no_amount), EXIT_PARSE);
+
+        let strict = Error::AtRow {
+            row: 2,
+            byte: None,
+            source: Box::new(Error::InsufficientFunds(2)),
+        };
+        assert_eq!(exit_code_for(&strict), EXIT_VALIDATION);
+        assert_eq!(
+            exit_code_for(&Error::Invariant {
+                client: 1,
+                reason: "held funds are negative",
+            }),
+            EXIT_VALIDATION
+        );
+
+        let json = serde_json::from_str::<u8>("x").unwrap_err();
+        assert_eq!(exit_code_for(&Error::Json(json)), EXIT_FAILURE);
+        assert_eq!(exit_code_for(&Error::SnapshotVersion), EXIT_FAILURE);
+    }
+}
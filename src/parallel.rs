@@ -0,0 +1,186 @@
+//! Processing of inputs using multiple threads.
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    panic,
+    sync::mpsc::{sync_channel, SyncSender},
+    thread,
+};
+
+use csv::StringRecord;
+
+use crate::{
+    deserialize_record, parse_error, Account, ClientId, Engine, ProcessOptions, Record, RecordType,
+    Result, TxId,
+};
+
+/// Number of records sent to a worker thread at once.
+const BATCH_SIZE: usize = 1024;
+
+/// Number of batches that can wait for a worker thread.
+const QUEUE_SIZE: usize = 16;
+
+/// Record together with its number and byte offset in the input.
+type Item = (u64, Option<u64>, Record);
+
+/// Records sent to a worker thread at once.
+type Batch = Vec<Item>;
+
+/// Process CSV data coming from any reader using multiple threads.
+///
+/// Records are partitioned by `client % threads` and each partition is
+/// processed by a separate [`Engine`] running on its own thread. The
+/// input is parsed on the current thread. Since all records of a client
+/// end up in the same partition, in the same order, the result is equal
+/// to the one of [`process_reader`](crate::process_reader).
+///
+/// Records of different clients interact only through transaction
+/// identifiers. Disputes, resolves and chargebacks referencing a
+/// transaction of a different client are sent to the partition of that
+/// client where they are rejected just like in serial processing. If a
+/// deposit or withdrawal reuses an identifier of a different client's
+/// transaction the partitions are merged and the rest of the input is
+/// processed serially on the current thread.
+///
+/// If `threads` is zero a single worker thread is used. When processing
+/// fails the returned error is the first one detected, which is not
+/// necessarily the first one in the input.
+///
+/// The default options are used, see [`process_parallel_with`] for
+/// processing inputs using different options.
+///
+/// Parsing and distributing records adds overhead thus this function
+/// can pay off only for big inputs on machines with many cores. Use
+/// `cargo bench --bench parallel` to find the crossover point of a
+/// machine.
+pub fn process_parallel<R: Read>(reader: R, threads: usize) -> Result<HashMap<ClientId, Account>> {
+    process_parallel_with(reader, threads, &ProcessOptions::default())
+}
+
+/// Process CSV data coming from any reader using given options and
+/// multiple threads.
+///
+/// Works like [`process_parallel`]. Each worker thread uses its own
+/// engine with the options. The records are always applied in the
+/// order of the input, i.e. [`EngineConfig::two_pass`] and
+/// [`EngineConfig::skip_unknown_types`] are not supported.
+///
+/// [`EngineConfig::two_pass`]: crate::EngineConfig::two_pass
+/// [`EngineConfig::skip_unknown_types`]: crate::EngineConfig::skip_unknown_types
+pub fn process_parallel_with<R: Read>(
+    reader: R,
+    threads: usize,
+    options: &ProcessOptions,
+) -> Result<HashMap<ClientId, Account>> {
+    let threads = threads.max(1);
+    let (mut rdr, headers) = options.config().csv_reader(reader)?;
+    thread::scope(|scope| {
+        let (senders, workers): (Vec<_>, Vec<_>) = (0..threads)
+            .map(|_| {
+                let (sender, receiver) = sync_channel::<Batch>(QUEUE_SIZE);
+                let worker = scope.spawn(move || {
+                    let mut engine = Engine::with_options(options.clone());
+                    for batch in receiver {
+                        for (row, byte, record) in batch {
+                            engine.apply_at(row, byte, record, &mut ())?;
+                        }
+                    }
+                    Ok(engine)
+                });
+                (sender, worker)
+            })
+            .unzip();
+
+        let dispatched = dispatch(&mut rdr, &headers, senders);
+
+        let mut engine = Engine::with_options(options.clone());
+        let mut result = Ok(());
+        for worker in workers {
+            match worker.join() {
+                Ok(Ok(partition)) => {
+                    // partitions do not share clients nor transactions
+                    engine.accounts.extend(partition.accounts);
+                    engine.txns.extend(partition.txns);
+                    engine.summaries.extend(partition.summaries);
+                }
+                Ok(Err(error)) => result = result.and(Err(error)),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+        let Some((mut row, byte, record)) = result.and(dispatched)? else {
+            return Ok(engine.into_accounts());
+        };
+
+        engine.apply_at(row, byte, record, &mut ())?;
+        let mut fields = StringRecord::new();
+        while rdr
+            .read_record(&mut fields)
+            .map_err(|error| parse_error(error, row + 1))?
+        {
+            row += 1;
+            let record = deserialize_record(&fields, &headers, row)?;
+            let byte = fields.position().map(csv::Position::byte);
+            engine.apply_at(row, byte, record, &mut ())?;
+        }
+        Ok(engine.into_accounts())
+    })
+}
+
+/// Parses records and sends them in batches to the worker threads.
+///
+/// Returns the first deposit or withdrawal reusing an identifier of a
+/// different client's transaction after sending all preceding records.
+/// Such a record and the rest of the input have to be processed
+/// serially. Stops early, without an error, if any worker has stopped
+/// as its error is reported when joining the worker.
+fn dispatch<R: Read>(
+    rdr: &mut csv::Reader<R>,
+    headers: &StringRecord,
+    senders: Vec<SyncSender<Batch>>,
+) -> Result<Option<Item>> {
+    let mut batches = senders
+        .iter()
+        .map(|_| Vec::with_capacity(BATCH_SIZE))
+        .collect::<Vec<_>>();
+    // clients of all deposits and withdrawals, including rejected ones
+    let mut owners = HashMap::<TxId, ClientId>::new();
+    let mut fields = StringRecord::new();
+    let mut row = 0;
+    let mut shared = None;
+    while rdr
+        .read_record(&mut fields)
+        .map_err(|error| parse_error(error, row + 1))?
+    {
+        row += 1;
+        let record = deserialize_record(&fields, headers, row)?;
+        let byte = fields.position().map(csv::Position::byte);
+        let owner = match record.kind {
+            RecordType::Deposit | RecordType::Withdrawal => {
+                let owner = *owners.entry(record.tx).or_insert(record.client);
+                if owner != record.client {
+                    shared = Some((row, byte, record));
+                    break;
+                }
+                owner
+            }
+            // rejected by the partition of the owner as a mismatch
+            _ => owners.get(&record.tx).copied().unwrap_or(record.client),
+        };
+        let partition = owner as usize % senders.len();
+        let batch = &mut batches[partition];
+        batch.push((row, byte, record));
+        if batch.len() == BATCH_SIZE {
+            let batch = std::mem::replace(batch, Vec::with_capacity(BATCH_SIZE));
+            if senders[partition].send(batch).is_err() {
+                return Ok(None);
+            }
+        }
+    }
+    for (sender, batch) in senders.iter().zip(batches) {
+        if sender.send(batch).is_err() {
+            return Ok(None);
+        }
+    }
+    Ok(shared)
+}
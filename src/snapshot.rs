@@ -0,0 +1,124 @@
+//! Persisting the state of the engine between runs.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{Account, Amounts, ClientId, Engine, Error, Result, StoredTx, Summary, TxId};
+
+/// First line of every snapshot file.
+///
+/// Needs to be changed whenever the format of the snapshot changes.
+const MAGIC: &str = "tx-engine snapshot v3";
+
+/// State of the engine which can be persisted and restored later.
+///
+/// Contains all accounts, their summaries and transactions that can be
+/// referenced by disputes (including their dispute states). The
+/// options of the engine are not included.
+///
+/// The snapshot can be stored in any format supported by `serde` that
+/// allows maps with integer keys, e.g. JSON.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Snapshot {
+    #[serde(serialize_with = "serialize_accounts")]
+    accounts: HashMap<ClientId, Account>,
+    txns: HashMap<TxId, StoredTx>,
+    #[serde(default)]
+    summaries: HashMap<ClientId, Summary>,
+}
+
+/// Persisted state of an account.
+///
+/// Unlike the output of [`Account`] the amounts are not rounded and the
+/// total is not stored, thus accounts whose total overflows can be
+/// persisted as well. It is read back as [`Account`] which ignores the
+/// total of snapshots saved with rounded accounts.
+#[derive(Serialize)]
+struct StoredAccount<'a> {
+    client: ClientId,
+    #[serde(flatten)]
+    amounts: &'a Amounts,
+    locked: bool,
+    disputes_opened: u64,
+    disputes_resolved: u64,
+    chargebacks: u64,
+    locked_by: Option<TxId>,
+}
+
+/// Serializes accounts without going through the output format.
+fn serialize_accounts<S: Serializer>(
+    accounts: &HashMap<ClientId, Account>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_map(accounts.iter().map(|(client, account)| {
+        let account = StoredAccount {
+            client: account.client,
+            amounts: &account.amounts,
+            locked: account.locked,
+            disputes_opened: account.disputes_opened,
+            disputes_resolved: account.disputes_resolved,
+            chargebacks: account.chargebacks,
+            locked_by: account.locked_by,
+        };
+        (client, account)
+    }))
+}
+
+impl Engine {
+    /// Returns a copy of the state of the engine.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            accounts: self.accounts.clone(),
+            txns: self.txns.clone(),
+            summaries: self.summaries.clone(),
+        }
+    }
+
+    /// Creates an engine with the state restored from a snapshot
+    /// returned by [`Engine::snapshot`].
+    ///
+    /// The engine uses default options.
+    pub fn restore(snapshot: Snapshot) -> Self {
+        Self {
+            accounts: snapshot.accounts,
+            txns: snapshot.txns,
+            summaries: snapshot.summaries,
+            ..Default::default()
+        }
+    }
+
+    /// Saves the state of the engine to a file.
+    ///
+    /// The snapshot contains all accounts, their summaries and
+    /// transactions that can be referenced by disputes. The options are
+    /// not stored.
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{MAGIC}")?;
+        serde_json::to_writer(&mut writer, &self.snapshot())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Creates an engine with the state restored from a file saved by
+    /// [`Engine::save_snapshot`].
+    ///
+    /// The engine uses default options. If the file is not a snapshot
+    /// or it has been saved by an incompatible version
+    /// [`Error::SnapshotVersion`] is returned.
+    pub fn load_snapshot(path: impl AsRef<Path>) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = vec![];
+        reader.read_until(b'\n', &mut magic)?;
+        if magic.strip_suffix(b"\n") != Some(MAGIC.as_bytes()) {
+            return Err(Error::SnapshotVersion);
+        }
+        Ok(Self::restore(serde_json::from_reader(reader)?))
+    }
+}
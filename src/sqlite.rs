@@ -0,0 +1,94 @@
+//! Processing of records stored in SQLite databases.
+
+use std::collections::HashMap;
+
+use rusqlite::{types::ValueRef, Connection, Row};
+use rust_decimal::Decimal;
+
+use crate::{parse_amount, Account, ClientId, Engine, Error, Record, RecordType, Result};
+
+/// Process records returned by the SQLite query.
+///
+/// The query must return the type, client, transaction identifier and
+/// amount of the records, in this order. Column names are not checked,
+/// thus the query can select them from any table, e.g.
+/// `SELECT kind, client_id, id, amount FROM transactions ORDER BY id`.
+/// Records are applied in the order of the rows.
+///
+/// Amounts should be stored as text or integers to be read exactly.
+/// Floating point amounts are accepted but converted to [`Decimal`]
+/// which may not preserve their exact value. Empty amounts are given
+/// as `NULL`.
+pub fn process_sqlite(conn: &Connection, query: &str) -> Result<HashMap<ClientId, Account>> {
+    let mut statement = conn.prepare(query)?;
+    let mut rows = statement.query(())?;
+    let mut engine = Engine::new();
+    let mut row = 0;
+    while let Some(fields) = rows.next()? {
+        row += 1;
+        let record = read_record(fields, row)?;
+        engine.apply_at(row, None, record, &mut ())?;
+    }
+    Ok(engine.into_accounts())
+}
+
+/// Converts the row returned by the query to a record.
+fn read_record(fields: &Row, row: u64) -> Result<Record> {
+    let invalid = |column, value: ValueRef| Error::InvalidField {
+        row,
+        column,
+        value: text(value),
+    };
+    let kind = fields.get_ref(0)?;
+    let kind = kind
+        .as_str()
+        .ok()
+        .and_then(RecordType::from_name)
+        .ok_or_else(|| invalid("type", kind))?;
+    let client = fields.get_ref(1)?;
+    let client = integer(client).ok_or_else(|| invalid("client", client))?;
+    let tx = fields.get_ref(2)?;
+    let tx = integer(tx).ok_or_else(|| invalid("tx", tx))?;
+    let amount = match fields.get_ref(3)? {
+        ValueRef::Null => None,
+        ValueRef::Integer(amount) => Some(amount.into()),
+        ValueRef::Text(amount) => Some(
+            std::str::from_utf8(amount)
+                .ok()
+                .and_then(|amount| parse_amount(amount.trim()).ok())
+                .ok_or_else(|| invalid("amount", ValueRef::Text(amount)))?,
+        ),
+        ValueRef::Real(amount) => Some(
+            Decimal::try_from(amount)
+                .map(|amount| amount.normalize())
+                .map_err(|_| invalid("amount", ValueRef::Real(amount)))?,
+        ),
+        value @ ValueRef::Blob(_) => return Err(invalid("amount", value)),
+    };
+    Ok(Record {
+        kind,
+        client,
+        tx,
+        amount,
+    })
+}
+
+/// Converts the integer value returning `None` if it is not an integer
+/// or it is out of range, e.g. a negative client identifier.
+fn integer<T: TryFrom<i64>>(value: ValueRef) -> Option<T> {
+    value
+        .as_i64()
+        .ok()
+        .and_then(|value| T::try_from(value).ok())
+}
+
+/// Formats the value for error messages.
+fn text(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(value) => value.to_string(),
+        ValueRef::Real(value) => value.to_string(),
+        ValueRef::Text(value) => String::from_utf8_lossy(value).into_owned(),
+        ValueRef::Blob(_) => "<blob>".into(),
+    }
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "async")]
+
+use std::path::PathBuf;
+
+use rstest::rstest;
+use testresult::TestResult;
+use tx_engine::{
+    process, process_async, process_async_with, process_stream, process_with, Error,
+    ProcessOptions, Record, RecordType,
+};
+
+#[rstest]
+#[tokio::test]
+async fn matches_sync(#[files("tests/test-cases/*.input.csv")] path: PathBuf) -> TestResult {
+    let file = tokio::fs::File::open(&path).await?;
+    let output = process_async(tokio::io::BufReader::new(file)).await?;
+
+    assert_eq!(
+        output,
+        process(&path)?,
+        "async processing must be equal with the sync one"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_options() -> TestResult {
+    let options = ProcessOptions::new().delimiter(b'\t');
+    let file = tokio::fs::File::open("tests/test-cases/options/tab-separated.input.tsv").await?;
+    assert_eq!(
+        process_async_with(tokio::io::BufReader::new(file), &options).await?,
+        process("tests/test-cases/multiple-accounts.input.csv")?
+    );
+
+    let input = "tests/test-cases/options/no-headers.input.csv";
+    let options = ProcessOptions::new().has_headers(false);
+    let file = tokio::fs::File::open(input).await?;
+    assert_eq!(
+        process_async_with(tokio::io::BufReader::new(file), &options).await?,
+        process_with(input, &options)?
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn parse_error_line() {
+    let input = "type,client,tx,amount\n\ndeposit,1,1,1.0\ntransfer,1,2,1.0\n";
+    let result = process_async(input.as_bytes()).await;
+    assert!(matches!(
+        result,
+        Err(Error::Parse {
+            line: 4,
+            row: 2,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn stream_of_records() -> TestResult {
+    let record = |kind, tx, amount: Option<&str>| -> tx_engine::Result<Record> {
+        Ok(Record {
+            kind,
+            client: 1,
+            tx,
+            amount: amount.map(|amount| amount.parse().expect("amount to be valid")),
+        })
+    };
+    let records = vec![
+        record(RecordType::Deposit, 1, Some("2.0")),
+        record(RecordType::Deposit, 2, Some("1.5")),
+        record(RecordType::Dispute, 1, None),
+    ];
+
+    let accounts = process_stream(futures::stream::iter(records)).await?;
+    let amounts = &accounts[&1].amounts;
+    assert_eq!(amounts.available, "1.5".parse()?);
+    assert_eq!(amounts.held, "2.0".parse()?);
+    Ok(())
+}
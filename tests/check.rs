@@ -1,3 +1,5 @@
+mod common;
+
 use std::path::PathBuf;
 
 use rstest::rstest;
@@ -5,24 +7,25 @@ use testresult::TestResult;
 
 #[rstest]
 fn main(#[files("tests/test-cases/*.input.csv")] path: PathBuf) -> TestResult {
-    use std::collections::HashMap;
-
     use csv::{Trim, Writer};
-    use tx_engine::{process, Account, ClientId};
+    use tx_engine::{process, process_reader, process_records, Record};
 
     let output = PathBuf::from(path.display().to_string().replace(".input.", ".output."));
     eprintln!("found path: {path:?} output: {output:?}");
 
-    let mut rdr = csv::ReaderBuilder::new()
-        .trim(Trim::All)
-        .from_path(output)?;
+    let accounts = common::read_accounts(output)?;
+    let output = process(&path)?;
 
-    let mut accounts = HashMap::<ClientId, Account>::new();
-    for item in rdr.deserialize() {
-        let item: Account = item?;
-        accounts.entry(item.client).or_insert(item);
-    }
-    let output = process(path)?;
+    // Reading from any reader must behave identically to reading from a path.
+    assert_eq!(output, process_reader(std::fs::File::open(&path)?)?);
+
+    // Already parsed records must produce the same accounts.
+    let records = csv::ReaderBuilder::new()
+        .trim(Trim::All)
+        .from_path(&path)?
+        .deserialize()
+        .collect::<Result<Vec<Record>, _>>()?;
+    assert_eq!(output, process_records(records)?);
 
     // Try to serialize all records.
     // This test prevents subtle serialization issues from appearing at runtime.
@@ -40,3 +43,25 @@ fn main(#[files("tests/test-cases/*.input.csv")] path: PathBuf) -> TestResult {
     );
     Ok(())
 }
+
+#[cfg(feature = "compression")]
+#[test]
+fn gzip_input() -> TestResult {
+    let expected = common::read_accounts("tests/test-cases/multiple-accounts.output.csv")?;
+    let output = tx_engine::process("tests/test-cases/compressed/multiple-accounts.input.csv.gz")?;
+    assert_eq!(output, expected);
+    Ok(())
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn gzip_magic_bytes() -> TestResult {
+    // detected by the contents since the extension is not `.gz`
+    let output =
+        tx_engine::process("tests/test-cases/compressed/multiple-accounts.input.csv.gzip")?;
+    assert_eq!(
+        output,
+        tx_engine::process("tests/test-cases/multiple-accounts.input.csv")?
+    );
+    Ok(())
+}
@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+
+use assert_cmd::Command;
+use rstest::rstest;
+use testresult::TestResult;
+use tx_engine::{process, Account};
+
+const INPUT: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\nwithdrawal, 1, 2, 0.5\n";
+
+const OUTPUT: &str = "client,available,held,total,locked\n1,0.5000,0.0000,0.5000,false\n";
+
+#[test]
+fn stdin_dash() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .arg("-")
+        .write_stdin(INPUT)
+        .assert()
+        .success()
+        .stdout(OUTPUT);
+    Ok(())
+}
+
+#[test]
+fn stdin_no_argument() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .write_stdin(INPUT)
+        .assert()
+        .success()
+        .stdout(OUTPUT);
+    Ok(())
+}
+
+#[test]
+fn file_argument() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .arg("tests/test-cases/with-spaces.input.csv")
+        .assert()
+        .success()
+        .stdout(OUTPUT);
+    Ok(())
+}
+
+#[test]
+fn multiple_files() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .arg("tests/test-cases/multiple-files/day-1.input.csv")
+        .arg("tests/test-cases/multiple-files/day-2.input.csv")
+        .assert()
+        .success()
+        .stdout("client,available,held,total,locked\n1,1.5000,1.0000,2.5000,false\n");
+    Ok(())
+}
+
+#[test]
+fn sorted_output() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .write_stdin("type,client,tx,amount\ndeposit,3,1,3\ndeposit,1,2,1\ndeposit,2,3,2\n")
+        .assert()
+        .success()
+        .stdout(
+            "client,available,held,total,locked\n1,1.0000,0.0000,1.0000,false\n2,2.0000,0.0000,2.0000,false\n3,3.0000,0.0000,3.0000,false\n",
+        );
+    Ok(())
+}
+
+#[test]
+fn deterministic_output() -> TestResult {
+    let run = || -> TestResult<Vec<u8>> {
+        Ok(Command::cargo_bin("tx-engine")?
+            .arg("tests/test-cases/cli/many-clients.input.csv")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone())
+    };
+    assert_eq!(run()?, run()?);
+    Ok(())
+}
+
+#[test]
+fn ascending_clients() -> TestResult {
+    let output = Command::cargo_bin("tx-engine")?
+        .arg("tests/test-cases/cli/many-clients.input.csv")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let clients = String::from_utf8(output)?
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').next().unwrap_or_default().parse())
+        .collect::<Result<Vec<u16>, _>>()?;
+    assert_eq!(clients.len(), 40);
+    assert!(clients.windows(2).all(|pair| pair[0] < pair[1]));
+    Ok(())
+}
+
+#[test]
+fn dispute_unknown_client() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .write_stdin("type,client,tx,amount\ndispute,99,1,\n")
+        .assert()
+        .success()
+        .stdout("client,available,held,total,locked\n");
+    Ok(())
+}
+
+#[test]
+fn rejected_withdrawal_unknown_client() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .write_stdin("type,client,tx,amount\nwithdrawal,99,1,1.0\n")
+        .assert()
+        .success()
+        .stdout("client,available,held,total,locked\n");
+    Ok(())
+}
+
+#[test]
+fn validate() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .args(["--validate", "tests/test-cases/dispute-ok.input.csv"])
+        .assert()
+        .success()
+        .stdout("client,available,held,total,locked\n1,0.0000,1.0000,1.0000,false\n");
+    Ok(())
+}
+
+#[test]
+fn unknown_type() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .arg("tests/test-cases/options/unknown-type.input.csv")
+        .assert()
+        .success()
+        .stdout("client,available,held,total,locked\n1,3.0000,0.0000,3.0000,false\n");
+    Ok(())
+}
+
+/// Returns an empty directory for the output files of a test.
+fn output_dir(name: &str) -> std::io::Result<std::path::PathBuf> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Returns names of all files in the directory.
+fn file_names(dir: &std::path::Path) -> std::io::Result<Vec<String>> {
+    std::fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect()
+}
+
+#[test]
+fn output_file() -> TestResult {
+    let dir = output_dir("output-file")?;
+    let path = dir.join("output.csv");
+    Command::cargo_bin("tx-engine")?
+        .arg("-o")
+        .arg(&path)
+        .write_stdin(INPUT)
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(std::fs::read_to_string(&path)?, OUTPUT);
+    // the temporary file has been renamed
+    assert_eq!(file_names(&dir)?, ["output.csv"]);
+    Ok(())
+}
+
+#[test]
+fn rejected_output() -> TestResult {
+    let dir = output_dir("rejected-output")?;
+    let path = dir.join("rejected.csv");
+    Command::cargo_bin("tx-engine")?
+        .arg("--rejected-output")
+        .arg(&path)
+        .arg("tests/test-cases/cli/rejected-output.input.csv")
+        .assert()
+        .success()
+        .stdout(
+            "client,available,held,total,locked\n\
+             1,1.0000,0.0000,1.0000,false\n\
+             2,3.0000,0.0000,3.0000,false\n",
+        );
+
+    let expected = std::fs::read_to_string("tests/test-cases/cli/rejected-output.rejected.csv")?;
+    let rejected = std::fs::read_to_string(&path)?;
+    assert_eq!(rejected.lines().count(), expected.lines().count());
+    for (line, expected) in rejected.lines().zip(expected.lines()) {
+        assert_eq!(line, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn output_file_unreadable_input() -> TestResult {
+    let dir = output_dir("output-file-unreadable-input")?;
+    let path = dir.join("output.csv");
+    Command::cargo_bin("tx-engine")?
+        .arg("--output")
+        .arg(&path)
+        .arg("tests/test-cases/does-not-exist.input.csv")
+        .assert()
+        .failure();
+    assert!(file_names(&dir)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn output_file_replaced() -> TestResult {
+    let dir = output_dir("output-file-replaced")?;
+    let path = dir.join("output.csv");
+    std::fs::write(&path, "previous")?;
+    Command::cargo_bin("tx-engine")?
+        .arg("--output")
+        .arg(&path)
+        .write_stdin("type,client,tx,amount\ndeposit,1,1,\n")
+        .assert()
+        .failure();
+    assert_eq!(std::fs::read_to_string(&path)?, "previous");
+
+    Command::cargo_bin("tx-engine")?
+        .arg("--output")
+        .arg(&path)
+        .write_stdin(INPUT)
+        .assert()
+        .success();
+    assert_eq!(std::fs::read_to_string(&path)?, OUTPUT);
+    Ok(())
+}
+
+#[test]
+fn output_file_not_created_on_error() -> TestResult {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("not-created.csv");
+    let _ = std::fs::remove_file(&path);
+    Command::cargo_bin("tx-engine")?
+        .arg("--output")
+        .arg(&path)
+        .write_stdin("type,client,tx,amount\ndeposit,1,1,\n")
+        .assert()
+        .failure();
+    assert!(!path.exists());
+    Ok(())
+}
+
+#[test]
+fn exit_code_missing_file() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .arg("tests/test-cases/does-not-exist.input.csv")
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+#[test]
+fn exit_code_parse_error() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .arg("tests/test-cases/invalid-field/non-numeric-amount.input.csv")
+        .assert()
+        .code(3);
+    Ok(())
+}
+
+/// Parses every line of the standard error as a JSON object.
+fn json_lines(stderr: &[u8]) -> serde_json::Result<Vec<serde_json::Value>> {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .map(serde_json::from_str)
+        .collect()
+}
+
+#[test]
+fn errors_format_json() -> TestResult {
+    let output = Command::cargo_bin("tx-engine")?
+        .args(["--errors-format", "json"])
+        .arg("tests/test-cases/cli/rejections.input.csv")
+        .assert()
+        .success()
+        .stdout("client,available,held,total,locked\n1,1.0000,0.0000,1.0000,false\n")
+        .get_output()
+        .clone();
+
+    let lines = json_lines(&output.stderr)?;
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["reason"], "insufficient_funds");
+    assert_eq!(lines[0]["row"], 2);
+    assert_eq!(lines[0]["tx"], 2);
+    assert_eq!(lines[0]["client"], 1);
+    assert_eq!(lines[1]["reason"], "tx_not_found");
+    assert_eq!(lines[1]["tx"], 9);
+    assert!(lines[1]["message"].is_string());
+    Ok(())
+}
+
+#[test]
+fn errors_format_json_fatal() -> TestResult {
+    let output = Command::cargo_bin("tx-engine")?
+        .args(["--errors-format", "json", "-"])
+        .write_stdin("type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,\n")
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+
+    let lines = json_lines(&output.stderr)?;
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["reason"], "deposit_no_amount");
+    assert_eq!(lines[0]["row"], 2);
+    assert_eq!(lines[0]["tx"], 2);
+    assert!(lines[0]["client"].is_null());
+    Ok(())
+}
+
+#[test]
+fn lenient_fatal_error() -> TestResult {
+    let input = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,2,2,\ndeposit,3,3,1.0\n";
+    let output = Command::cargo_bin("tx-engine")?
+        .args(["--lenient", "--errors-format", "json", "-"])
+        .write_stdin(input)
+        .assert()
+        .code(3)
+        // records before the failing one have been applied
+        .stdout("client,available,held,total,locked\n1,1.0000,0.0000,1.0000,false\n")
+        .get_output()
+        .clone();
+    let lines = json_lines(&output.stderr)?;
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["reason"], "deposit_no_amount");
+
+    // without the flag nothing is written
+    Command::cargo_bin("tx-engine")?
+        .write_stdin(input)
+        .assert()
+        .code(3)
+        .stdout("");
+    Ok(())
+}
+
+#[rstest]
+#[case::two(&["--precision", "2"], "1,1.50,0.00,1.50,false\n")]
+#[case::four(&["--precision", "4"], "1,1.5050,0.0000,1.5050,false\n")]
+#[case::default(&[], "1,1.5050,0.0000,1.5050,false\n")]
+fn precision(#[case] args: &[&str], #[case] expected: &'static str) -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .args(args)
+        .write_stdin("type,client,tx,amount\ndeposit,1,1,1.5\ndeposit,1,2,0.005\n")
+        .assert()
+        .success()
+        .stdout(format!("client,available,held,total,locked\n{expected}"));
+    Ok(())
+}
+
+#[test]
+fn precision_out_of_range() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        // amounts are stored with four decimal places
+        .args(["--precision", "5", "-"])
+        .write_stdin(INPUT)
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+#[test]
+fn summary() -> TestResult {
+    let output = Command::cargo_bin("tx-engine")?
+        .args(["--summary", "tests/test-cases/cli/rejections.input.csv"])
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+
+    let summary: serde_json::Value = serde_json::from_slice(&output.stderr)?;
+    assert_eq!(summary["records"], 3);
+    assert_eq!(summary["records_by_type"]["deposit"], 1);
+    assert_eq!(summary["rejected_by_reason"]["insufficient_funds"], 1);
+    assert_eq!(summary["rejected_by_reason"]["tx_not_found"], 1);
+    assert_eq!(summary["accounts"], 1);
+    assert_eq!(summary["locked_accounts"], 0);
+    assert_eq!(summary["total"], "1");
+    Ok(())
+}
+
+#[test]
+fn extended_output() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .args([
+            "--extended-output",
+            "tests/test-cases/extended/two-disputes.input.csv",
+        ])
+        .assert()
+        .success()
+        .stdout(std::fs::read_to_string(
+            "tests/test-cases/extended/two-disputes.output.csv",
+        )?);
+    Ok(())
+}
+
+#[test]
+fn extended_output_json() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .args(["--extended-output", "--format", "json", "-"])
+        .write_stdin(INPUT)
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+#[rstest]
+#[case::escaped("\\t")]
+#[case::tab("\t")]
+fn tab_separated(#[case] delimiter: &str) -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .args(["--delimiter", delimiter])
+        .arg("tests/test-cases/options/tab-separated.input.tsv")
+        .assert()
+        .success()
+        .stdout(
+            "client\tavailable\theld\ttotal\tlocked\n\
+             1\t0.5000\t0.0000\t0.5000\tfalse\n\
+             2\t1.5000\t0.0000\t1.5000\tfalse\n",
+        );
+    Ok(())
+}
+
+#[test]
+fn unsupported_delimiter() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .args(["--delimiter", ":", "-"])
+        .write_stdin(INPUT)
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn compressed_input() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .arg("tests/test-cases/compressed/multiple-accounts.input.csv.gz")
+        .assert()
+        .success()
+        .stdout(
+            "client,available,held,total,locked\n\
+             1,0.5000,0.0000,0.5000,false\n\
+             2,1.5000,0.0000,1.5000,false\n",
+        );
+    Ok(())
+}
+
+#[test]
+fn no_headers() -> TestResult {
+    Command::cargo_bin("tx-engine")?
+        .arg("--no-headers")
+        .write_stdin("deposit,1,1,1.0\nwithdrawal,1,2,0.5\n")
+        .assert()
+        .success()
+        .stdout(OUTPUT);
+    Ok(())
+}
+
+#[test]
+fn jsonl_format() -> TestResult {
+    let input = "tests/test-cases/cli/many-clients.input.csv";
+    let output = Command::cargo_bin("tx-engine")?
+        .args(["--format", "jsonl", input])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let mut accounts = HashMap::new();
+    for line in String::from_utf8(output)?.lines() {
+        let account: Account = serde_json::from_str(line)?;
+        assert!(accounts.insert(account.client, account).is_none());
+    }
+    assert_eq!(accounts, process(input)?);
+    Ok(())
+}
+
+#[test]
+fn json_format() -> TestResult {
+    let output = Command::cargo_bin("tx-engine")?
+        .args(["--format", "json", "tests/test-cases/dispute-ok.input.csv"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let accounts: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(
+        accounts,
+        serde_json::json!([{
+            "client": 1,
+            "available": "0.0000",
+            "held": "1.0000",
+            "total": "1.0000",
+            "locked": false,
+        }])
+    );
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use std::{collections::HashMap, path::Path};
+
+use csv::Trim;
+use tx_engine::{Account, ClientId};
+
+/// Reads expected accounts from a CSV file.
+pub fn read_accounts(path: impl AsRef<Path>) -> csv::Result<HashMap<ClientId, Account>> {
+    let mut rdr = csv::ReaderBuilder::new().trim(Trim::All).from_path(path)?;
+
+    let mut accounts = HashMap::<ClientId, Account>::new();
+    for item in rdr.deserialize() {
+        let item: Account = item?;
+        accounts.entry(item.client).or_insert(item);
+    }
+    Ok(accounts)
+}
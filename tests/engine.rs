@@ -0,0 +1,644 @@
+mod common;
+
+use std::{collections::HashMap, sync::Arc};
+
+use rstest::rstest;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use testresult::TestResult;
+use tx_engine::{
+    process, process_str, process_with, process_with_observer, process_with_report,
+    sorted_accounts, write_accounts_json, Account, Engine, EngineObserver, Error, Event, Precision,
+    ProcessOptions, Record, RecordType, Rejection, RejectionReason, TxKind, TxState,
+    VecDiagnostics, WithdrawalDisputePolicy,
+};
+
+#[test]
+fn state_is_shared_between_files() -> TestResult {
+    let mut engine = Engine::new();
+    engine.process_file("tests/test-cases/multiple-files/day-1.input.csv")?;
+    engine.process_file("tests/test-cases/multiple-files/day-2.input.csv")?;
+
+    let expected = common::read_accounts("tests/test-cases/multiple-files/expected.output.csv")?;
+    assert_eq!(engine.into_accounts(), expected);
+
+    // the dispute references a transaction from the first file
+    assert_ne!(
+        process("tests/test-cases/multiple-files/day-2.input.csv")?,
+        expected
+    );
+    Ok(())
+}
+
+#[test]
+fn snapshot_restores_state() -> TestResult {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("day-1.snapshot");
+
+    let mut engine = Engine::new();
+    engine.process_file("tests/test-cases/multiple-files/day-1.input.csv")?;
+    engine.save_snapshot(&path)?;
+
+    let mut engine = Engine::load_snapshot(&path)?;
+    engine.process_file("tests/test-cases/multiple-files/day-2.input.csv")?;
+
+    let mut single_pass = Engine::new();
+    single_pass.process_file("tests/test-cases/multiple-files/day-1.input.csv")?;
+    single_pass.process_file("tests/test-cases/multiple-files/day-2.input.csv")?;
+
+    assert_eq!(engine.into_accounts(), single_pass.into_accounts());
+    Ok(())
+}
+
+#[test]
+fn snapshot_round_trip() -> TestResult {
+    let input = std::fs::read_to_string("tests/test-cases/engine/rejections.input.csv")?;
+    let (header, records) = input.split_once('\n').expect("header line to exist");
+    let lines = records.lines().collect::<Vec<_>>();
+    let (first, second) = lines.split_at(lines.len() / 2);
+
+    let mut engine = Engine::new();
+    engine.process_reader(format!("{header}\n{}\n", first.join("\n")).as_bytes())?;
+    let snapshot = serde_json::to_string(&engine.snapshot())?;
+
+    let mut engine = Engine::restore(serde_json::from_str(&snapshot)?);
+    engine.process_reader(format!("{header}\n{}\n", second.join("\n")).as_bytes())?;
+    assert_eq!(engine.into_accounts(), tx_engine::process_str(&input)?);
+    Ok(())
+}
+
+#[test]
+fn snapshot_exact_amounts() -> TestResult {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("overflow.snapshot");
+    let max = Decimal::MAX.to_string();
+    let options = ProcessOptions::new().withdrawal_disputes(WithdrawalDisputePolicy::Reverse);
+
+    let mut engine = Engine::with_options(options);
+    // reversing the withdrawal holds its amount thus the total overflows
+    engine.process_reader(
+        format!(
+            "type,client,tx,amount\n\
+             deposit,1,1,{max}\nwithdrawal,1,2,{max}\ndispute,1,2,\ndeposit,1,3,{max}\n\
+             deposit,2,4,1.2345\n"
+        )
+        .as_bytes(),
+    )?;
+    assert!(engine.accounts()[&1].amounts.checked_total().is_none());
+    engine.save_snapshot(&path)?;
+
+    let restored = Engine::load_snapshot(&path)?;
+    assert_eq!(restored.accounts(), engine.accounts());
+    Ok(())
+}
+
+#[test]
+fn snapshot_bad_version() {
+    let result = Engine::load_snapshot("tests/test-cases/multiple-files/day-1.input.csv");
+    assert!(matches!(result, Err(tx_engine::Error::SnapshotVersion)));
+}
+
+#[test]
+fn process_many_files() -> TestResult {
+    let accounts = tx_engine::process_many([
+        "tests/test-cases/multiple-files/day-1.input.csv",
+        "tests/test-cases/multiple-files/day-2.input.csv",
+    ])?;
+
+    let expected = common::read_accounts("tests/test-cases/multiple-files/expected.output.csv")?;
+    assert_eq!(accounts, expected);
+    Ok(())
+}
+
+#[test]
+fn byte_order_mark() -> TestResult {
+    let input = "tests/test-cases/with-bom.input.csv";
+    assert!(std::fs::read(input)?.starts_with(b"\xef\xbb\xbf"));
+
+    // the mark must not end up in the name of the first column
+    let mut engine = Engine::with_options(ProcessOptions::new().reject_unknown_columns(true));
+    engine.process_file(input)?;
+    assert_eq!(
+        engine.into_accounts(),
+        common::read_accounts("tests/test-cases/with-bom.output.csv")?
+    );
+    Ok(())
+}
+
+#[test]
+fn stored_transactions() -> TestResult {
+    let mut engine = Engine::new();
+    engine.process_file("tests/test-cases/engine/transaction-states.input.csv")?;
+
+    let expected = [
+        (1, 1, "5.0", TxKind::Deposit, TxState::ChargedBack),
+        (2, 2, "3.0", TxKind::Deposit, TxState::Resolved),
+        (3, 2, "1.0", TxKind::Deposit, TxState::Disputed),
+        (4, 2, "0.5", TxKind::Withdrawal, TxState::Open),
+    ];
+    for (id, client, amount, kind, state) in expected {
+        let tx = engine.transaction(id).ok_or("transaction not found")?;
+        assert_eq!(tx.client(), client);
+        assert_eq!(tx.amount(), amount.parse()?);
+        assert_eq!(tx.kind(), kind);
+        assert_eq!(tx.state(), state);
+    }
+
+    // rejected withdrawal is not stored
+    assert!(engine.transaction(5).is_none());
+    Ok(())
+}
+
+#[rstest]
+#[case::multiple_accounts("multiple-accounts")]
+#[case::chargeback_ok("chargeback-ok")]
+#[case::dispute_withdrawal_chargeback("dispute-withdrawal-chargeback")]
+#[case::transaction_states("engine/transaction-states")]
+fn float_amounts(#[case] name: &str) -> TestResult {
+    let input = format!("tests/test-cases/{name}.input.csv");
+    let mut engine = Engine::<f64>::default();
+    engine.process_file(&input)?;
+
+    let to_f64 = |account: Account| {
+        let amounts = &account.amounts;
+        (
+            amounts.available.to_f64(),
+            amounts.held.to_f64(),
+            account.locked,
+        )
+    };
+    let expected = process(&input)?
+        .into_iter()
+        .map(|(client, account)| (client, to_f64(account)))
+        .collect::<HashMap<_, _>>();
+    let accounts = engine
+        .into_accounts()
+        .into_iter()
+        .map(|(client, account)| {
+            let amounts = &account.amounts;
+            let funds = (Some(amounts.available), Some(amounts.held), account.locked);
+            (client, funds)
+        })
+        .collect::<HashMap<_, _>>();
+    assert_eq!(accounts, expected);
+    Ok(())
+}
+
+#[test]
+fn ledger() -> TestResult {
+    let mut engine = Engine::new();
+    engine.process_reader(
+        "type,client,tx,amount\n\
+         deposit,1,1,2.0\n\
+         deposit,2,2,1.5\n\
+         dispute,1,1,\n\
+         resolve,1,1,\n\
+         withdrawal,2,3,5.0\n"
+            .as_bytes(),
+    )?;
+
+    let ledger = engine
+        .into_ledger()
+        .into_iter()
+        .map(|(id, tx)| (id, (tx.client(), tx.amount(), tx.state())))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(
+        ledger,
+        HashMap::from([
+            (1, (1, "2.0".parse()?, TxState::Resolved)),
+            (2, (2, "1.5".parse()?, TxState::Open)),
+        ])
+    );
+    Ok(())
+}
+
+#[test]
+fn open_disputes() -> TestResult {
+    let mut engine = Engine::new();
+    engine.process_reader(
+        "type,client,tx,amount\n\
+         deposit,1,1,2.0\n\
+         deposit,1,2,1.5\n\
+         deposit,2,3,4.0\n\
+         deposit,2,4,1.0\n\
+         dispute,1,1,\n\
+         dispute,2,3,\n\
+         dispute,2,4,\n\
+         resolve,2,4,\n"
+            .as_bytes(),
+    )?;
+
+    let mut disputes = engine.open_disputes().collect::<Vec<_>>();
+    disputes.sort_unstable_by_key(|(_, tx, _)| *tx);
+    assert_eq!(disputes, [(1, 1, "2.0".parse()?), (2, 3, "4.0".parse()?)]);
+    Ok(())
+}
+
+#[test]
+fn error_row() {
+    // the header is followed by two deposits and an empty line
+    let error = process("tests/test-cases/engine/missing-amount.input.csv")
+        .expect_err("the deposit without an amount to fail");
+    assert_eq!(
+        error.to_string(),
+        "tests/test-cases/engine/missing-amount.input.csv: \
+         Record 3: Deposit used but no amount is specified in transaction 3"
+    );
+    let Error::InFile { source, .. } = error else {
+        panic!("error without a path: {error}");
+    };
+    assert!(matches!(
+        *source,
+        Error::AtRow { row: 3, byte: Some(55), source } if matches!(*source, Error::DepositNoAmount(3))
+    ));
+}
+
+#[test]
+fn parse_error_row() {
+    // the CSV reader does not count empty lines thus only the record
+    // number is reliable
+    let result = process("tests/test-cases/engine/bad-type.input.csv");
+    assert!(matches!(
+        result,
+        Err(Error::InFile { source, .. }) if matches!(*source, Error::Parse { row: 2, .. })
+    ));
+}
+
+#[test]
+fn error_path() {
+    let path = "tests/test-cases/engine/does-not-exist.csv";
+    let error = process(path).expect_err("missing file to fail");
+    assert!(error.to_string().starts_with(path), "{error}");
+    assert!(matches!(
+        error,
+        Error::InFile { source, .. } if matches!(*source, Error::Io(_))
+    ));
+
+    let path = "tests/test-cases/engine/bad-type.input.csv";
+    let error = process(path).expect_err("corrupt file to fail");
+    assert!(error.to_string().starts_with(path), "{error}");
+}
+
+#[test]
+fn rejections_report() -> TestResult {
+    let input = "tests/test-cases/engine/rejections.input.csv";
+    let (accounts, report) = process_with_report(input)?;
+    assert_eq!(accounts, process(input)?);
+    let rejections = report.rejections;
+
+    let rejection = |row, kind, tx, client, amount: Option<&str>, reason| Rejection {
+        row,
+        tx,
+        client,
+        kind,
+        amount: amount.map(|amount| amount.parse().expect("amount to be valid")),
+        reason,
+    };
+    assert_eq!(
+        rejections,
+        [
+            rejection(
+                2,
+                RecordType::Withdrawal,
+                2,
+                1,
+                Some("5"),
+                RejectionReason::InsufficientFunds
+            ),
+            rejection(
+                3,
+                RecordType::Dispute,
+                99,
+                1,
+                None,
+                RejectionReason::TxNotFound
+            ),
+            rejection(
+                4,
+                RecordType::Resolve,
+                1,
+                1,
+                None,
+                RejectionReason::NotDisputed
+            ),
+            rejection(
+                6,
+                RecordType::Dispute,
+                3,
+                1,
+                None,
+                RejectionReason::NotDisputable
+            ),
+            rejection(
+                8,
+                RecordType::Dispute,
+                1,
+                2,
+                None,
+                RejectionReason::ClientMismatch
+            ),
+            rejection(
+                10,
+                RecordType::Dispute,
+                4,
+                2,
+                None,
+                RejectionReason::AlreadyDisputed
+            ),
+            rejection(
+                12,
+                RecordType::Deposit,
+                5,
+                2,
+                Some("1"),
+                RejectionReason::AccountLocked
+            ),
+            rejection(
+                13,
+                RecordType::Dispute,
+                4,
+                2,
+                None,
+                RejectionReason::ChargedBack
+            ),
+            rejection(
+                14,
+                RecordType::Deposit,
+                1,
+                1,
+                Some("1"),
+                RejectionReason::DuplicateTx
+            ),
+            rejection(
+                15,
+                RecordType::Deposit,
+                6,
+                1,
+                Some("-1"),
+                RejectionReason::NegativeAmount
+            ),
+            rejection(
+                16,
+                RecordType::Dispute,
+                1,
+                1,
+                None,
+                RejectionReason::InsufficientFunds
+            ),
+        ]
+    );
+    assert_eq!(
+        rejections[0].to_string(),
+        "Record 2 (transaction 2 of client 1) rejected: insufficient funds"
+    );
+    Ok(())
+}
+
+#[test]
+fn process_report() -> TestResult {
+    let (_, report) = process_with_report("tests/test-cases/engine/rejections.input.csv")?;
+
+    assert_eq!(report.records, 16);
+    assert_eq!(
+        report.records_by_type,
+        HashMap::from([
+            (RecordType::Deposit, 5),
+            (RecordType::Withdrawal, 2),
+            (RecordType::Dispute, 7),
+            (RecordType::Resolve, 1),
+            (RecordType::Chargeback, 1),
+        ])
+    );
+    assert_eq!(
+        report.rejected_by_reason,
+        HashMap::from([
+            (RejectionReason::InsufficientFunds, 2),
+            (RejectionReason::TxNotFound, 1),
+            (RejectionReason::NotDisputed, 1),
+            (RejectionReason::NotDisputable, 1),
+            (RejectionReason::ClientMismatch, 1),
+            (RejectionReason::AlreadyDisputed, 1),
+            (RejectionReason::AccountLocked, 1),
+            (RejectionReason::ChargedBack, 1),
+            (RejectionReason::DuplicateTx, 1),
+            (RejectionReason::NegativeAmount, 1),
+        ])
+    );
+    assert_eq!(report.rejections.len(), 11);
+    assert_eq!(report.accounts, 2);
+    assert_eq!(report.locked_accounts, 1);
+    // client 1 deposited 1.0 and withdrew 0.5, client 2 lost its only
+    // deposit with a chargeback
+    assert_eq!(report.total, Some("0.5".parse()?));
+    Ok(())
+}
+
+#[test]
+fn stats() -> TestResult {
+    let mut engine = Engine::new();
+    engine.process_file("tests/test-cases/engine/rejections.input.csv")?;
+
+    let stats = engine.stats();
+    assert_eq!(stats.applied, 5);
+    assert_eq!(stats.rejected, 11);
+    assert_eq!(stats.rejected, engine.rejections().len() as u64);
+    assert_eq!(stats.rejected_by_reason.len(), 10);
+    assert_eq!(
+        stats.rejected_by_reason[&RejectionReason::InsufficientFunds],
+        2
+    );
+    assert_eq!(stats.rejected_by_reason[&RejectionReason::TxNotFound], 1);
+    assert_eq!(stats.unknown_types, 0);
+    assert_eq!(stats.unexpected_amounts, 0);
+    Ok(())
+}
+
+#[test]
+fn event_sink() -> TestResult {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut engine = Engine::with_options(ProcessOptions::new().skip_unknown_types(true));
+    engine.set_event_sink(move |event| sender.send(event.clone()).expect("receiver to exist"));
+    engine.process_reader(
+        &b"type,client,tx,amount\n\
+           deposit,1,1,1.0\ndispute,1,2,\ndispute,1,1,5.0\nfee,1,3,0.1\n"[..],
+    )?;
+    drop(engine);
+
+    assert_eq!(
+        receiver.iter().collect::<Vec<_>>(),
+        [
+            Event::Rejected(Rejection {
+                row: 2,
+                tx: 2,
+                client: 1,
+                kind: RecordType::Dispute,
+                amount: None,
+                reason: RejectionReason::TxNotFound,
+            }),
+            Event::UnexpectedAmount {
+                row: 3,
+                tx: 1,
+                kind: RecordType::Dispute,
+            },
+            Event::UnknownRecordType {
+                row: 4,
+                value: "fee".into(),
+            },
+        ]
+    );
+    Ok(())
+}
+
+/// Counts applied and rejected records of each type.
+#[derive(Default)]
+struct CountingObserver {
+    applied: HashMap<RecordType, usize>,
+    rejected: HashMap<RecordType, usize>,
+}
+
+impl EngineObserver for CountingObserver {
+    fn on_applied(&mut self, record: &Record, _account: &Account) {
+        *self.applied.entry(record.kind).or_default() += 1;
+    }
+
+    fn on_rejected(&mut self, record: &Record, _reason: &RejectionReason) {
+        *self.rejected.entry(record.kind).or_default() += 1;
+    }
+}
+
+#[test]
+fn accounts_json() -> TestResult {
+    let accounts = process("tests/test-cases/engine/rejections.input.csv")?;
+    let mut json = vec![];
+    write_accounts_json(
+        &mut json,
+        &sorted_accounts(accounts.clone()),
+        Precision::default(),
+    )?;
+
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&json)?;
+    assert_eq!(entries.len(), accounts.len());
+    assert!(entries
+        .windows(2)
+        .all(|pair| pair[0]["client"].as_u64() < pair[1]["client"].as_u64()));
+    for entry in entries {
+        let amount = |field: &str| -> TestResult<Decimal> {
+            Ok(entry[field]
+                .as_str()
+                .ok_or("amount to be a string")?
+                .parse()?)
+        };
+        assert_eq!(amount("total")?, amount("available")? + amount("held")?);
+
+        let client = entry["client"].as_u64().ok_or("client to be a number")?;
+        let account = &accounts[&u16::try_from(client)?];
+        assert_eq!(amount("available")?, account.amounts.available);
+        assert_eq!(amount("held")?, account.amounts.held);
+        assert_eq!(entry["locked"], account.locked);
+    }
+    Ok(())
+}
+
+#[test]
+fn accounts_json_precision() -> TestResult {
+    let accounts = process_str("type,client,tx,amount\ndeposit,1,1,1.505\n")?;
+    let mut json = vec![];
+    let precision = Precision {
+        decimal_places: 2,
+        ..Default::default()
+    };
+    write_accounts_json(&mut json, accounts.values(), precision)?;
+    assert_eq!(
+        String::from_utf8(json)?,
+        r#"[{"client":1,"available":"1.50","held":"0.00","total":"1.50","locked":false}]"#
+            .to_owned()
+            + "\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn vec_diagnostics() -> TestResult {
+    let diagnostics = Arc::new(VecDiagnostics::new());
+    let options = ProcessOptions::new().diagnostics(diagnostics.clone());
+    process_with("tests/test-cases/engine/diagnostics.input.csv", &options)?;
+
+    assert_eq!(
+        diagnostics.rejections(),
+        [
+            Rejection {
+                row: 2,
+                tx: 2,
+                client: 1,
+                kind: RecordType::Withdrawal,
+                amount: Some(5.into()),
+                reason: RejectionReason::InsufficientFunds,
+            },
+            Rejection {
+                row: 3,
+                tx: 9,
+                client: 1,
+                kind: RecordType::Dispute,
+                amount: None,
+                reason: RejectionReason::TxNotFound,
+            },
+        ]
+    );
+    assert!(diagnostics.notes().is_empty());
+    Ok(())
+}
+
+#[test]
+fn observer_counts() -> TestResult {
+    let input = "tests/test-cases/engine/rejections.input.csv";
+    let mut observer = CountingObserver::default();
+    assert_eq!(
+        process_with_observer(input, &mut observer)?,
+        process(input)?
+    );
+
+    assert_eq!(
+        observer.applied,
+        HashMap::from([
+            (RecordType::Deposit, 2),
+            (RecordType::Withdrawal, 1),
+            (RecordType::Dispute, 1),
+            (RecordType::Chargeback, 1),
+        ])
+    );
+    // the failed withdrawal is a rejection
+    assert_eq!(
+        observer.rejected,
+        HashMap::from([
+            (RecordType::Deposit, 3),
+            (RecordType::Withdrawal, 1),
+            (RecordType::Dispute, 6),
+            (RecordType::Resolve, 1),
+        ])
+    );
+    Ok(())
+}
+
+#[test]
+fn on_apply_transcript() -> TestResult {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut engine = Engine::new();
+    engine.set_on_apply(move |record, account| {
+        let line = format!(
+            "{} {}: {} {}",
+            record.kind, record.tx, account.amounts.available, account.amounts.held
+        );
+        sender.send(line).expect("receiver to exist");
+    });
+    engine.process_file("tests/test-cases/engine/rejections.input.csv")?;
+    drop(engine);
+
+    assert_eq!(
+        receiver.iter().collect::<Vec<_>>(),
+        [
+            "deposit 1: 1 0",
+            "withdrawal 3: 0.5 0",
+            "deposit 4: 2 0",
+            "dispute 4: 0 2",
+            "chargeback 4: 0 0",
+        ]
+    );
+    Ok(())
+}
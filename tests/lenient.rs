@@ -0,0 +1,145 @@
+mod common;
+
+use std::sync::Arc;
+
+use rstest::rstest;
+use testresult::TestResult;
+use tx_engine::{
+    process, process_lenient, process_reader_lenient, process_reader_lenient_with, Error,
+    ProcessOptions, RejectionReason, VecDiagnostics,
+};
+
+#[test]
+fn bad_rows_are_skipped() -> TestResult {
+    let input = "tests/test-cases/lenient/bad-rows.input.csv";
+    let expected = common::read_accounts("tests/test-cases/lenient/bad-rows.output.csv")?;
+
+    assert!(process(input).is_err(), "strict processing must abort");
+
+    let (accounts, errors) = process_lenient(input)?;
+    assert_eq!(accounts, expected);
+
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].record, 2);
+    assert_eq!(errors[0].line.as_deref(), Some("deposit,1,2,abc"));
+    assert!(matches!(
+        &errors[0].error,
+        Error::InvalidField { row: 2, column: "amount", value } if value == "abc"
+    ));
+    assert_eq!(errors[1].record, 3);
+    assert_eq!(errors[1].line.as_deref(), Some("withdrawal,1,3,"));
+    assert!(matches!(errors[1].error, Error::WithdrawNoAmount(3)));
+    Ok(())
+}
+
+#[test]
+fn bad_amount_from_reader() -> TestResult {
+    let input = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,1.x\nwithdrawal,1,3,0.5\n";
+
+    let (accounts, errors) = process_reader_lenient(input.as_bytes())?;
+    assert_eq!(accounts[&1].amounts.available, "0.5".parse()?);
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].record, 2);
+    assert!(matches!(
+        errors[0].error,
+        Error::InvalidField {
+            column: "amount",
+            ..
+        }
+    ));
+    Ok(())
+}
+
+#[test]
+fn tab_separated() -> TestResult {
+    let input = "type\tclient\ttx\tamount\ndeposit\t1\t1\t1.0\nwithdrawal\t1\t2\t\n";
+    let options = ProcessOptions::new().delimiter(b'\t');
+
+    let (accounts, errors) = process_reader_lenient_with(input.as_bytes(), &options)?;
+    assert_eq!(accounts[&1].amounts.available, 1.into());
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].record, 2);
+    assert_eq!(errors[0].line.as_deref(), Some("withdrawal\t1\t2\t"));
+    assert!(matches!(errors[0].error, Error::WithdrawNoAmount(2)));
+    Ok(())
+}
+
+#[test]
+fn no_headers() -> TestResult {
+    let input = "deposit,1,1,1.0\nwithdrawal,1,2,\ndeposit,1,3,2.0\n";
+    let options = ProcessOptions::new().has_headers(false);
+
+    let (accounts, errors) = process_reader_lenient_with(input.as_bytes(), &options)?;
+    // the first record is not mistaken for the header line
+    assert_eq!(accounts[&1].amounts.available, 3.into());
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].record, 2);
+    assert_eq!(errors[0].line.as_deref(), Some("withdrawal,1,2,"));
+    assert!(matches!(errors[0].error, Error::WithdrawNoAmount(2)));
+
+    // without the option the first record is the header line
+    assert!(matches!(
+        process_reader_lenient(input.as_bytes()),
+        Err(Error::MissingColumns(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn rejection_rows() -> TestResult {
+    let input = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,x\nwithdrawal,1,3,5.0\n";
+    let diagnostics = Arc::new(VecDiagnostics::new());
+    let options = ProcessOptions::new().diagnostics(diagnostics.clone());
+
+    let (_, errors) = process_reader_lenient_with(input.as_bytes(), &options)?;
+    assert_eq!(errors.len(), 1);
+
+    let rejections = diagnostics.rejections();
+    assert_eq!(rejections.len(), 1);
+    assert_eq!(rejections[0].row, 3);
+    assert_eq!(rejections[0].reason, RejectionReason::InsufficientFunds);
+    Ok(())
+}
+
+#[rstest]
+#[case::non_numeric_amount("non-numeric-amount", "amount", "1.0O")]
+#[case::client_too_big("client-too-big", "client", "70000")]
+#[case::tx_too_big("tx-too-big", "tx", "4294967296")]
+fn invalid_field(
+    #[case] name: &str,
+    #[case] expected_column: &str,
+    #[case] expected_value: &str,
+) -> TestResult {
+    let input = format!("tests/test-cases/invalid-field/{name}.input.csv");
+
+    let error = match process(&input) {
+        Err(Error::InFile { source, .. }) => *source,
+        result => panic!("unexpected result: {result:?}"),
+    };
+    let Error::InvalidField { row, column, value } = &error else {
+        panic!("unexpected error: {error}");
+    };
+    assert_eq!(
+        (*row, *column, value.as_str()),
+        (2, expected_column, expected_value)
+    );
+
+    // in lenient mode the row is skipped
+    let (accounts, errors) = process_lenient(&input)?;
+    assert_eq!(accounts[&1].amounts.available, 3.into());
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].record, 2);
+    assert_eq!(errors[0].error.to_string(), error.to_string());
+    Ok(())
+}
+
+#[test]
+fn missing_file() {
+    assert!(matches!(
+        process_lenient("tests/test-cases/lenient/does-not-exist.csv"),
+        Err(Error::InFile { source, .. }) if matches!(*source, Error::Io(_))
+    ));
+}
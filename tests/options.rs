@@ -0,0 +1,359 @@
+mod common;
+
+use rstest::rstest;
+use testresult::TestResult;
+use tx_engine::{
+    process, process_client_with, process_str, process_with, DisputeOverdraftPolicy, Engine,
+    EngineConfig, Error, ExcessPrecisionPolicy, ProcessOptions, RecordType,
+    WithdrawalDisputePolicy,
+};
+
+/// Unwraps [`Error::InFile`] returned by functions processing files.
+fn without_path<T>(result: tx_engine::Result<T>) -> tx_engine::Result<T> {
+    result.map_err(|error| match error {
+        Error::InFile { path, source } => {
+            assert!(path.starts_with("tests/test-cases"));
+            *source
+        }
+        error => error,
+    })
+}
+
+#[rstest]
+#[case::locked_deposits("locked-deposits", ProcessOptions::new().locked_deposits(true))]
+#[case::allow_duplicate_tx("allow-duplicate-tx", ProcessOptions::new().reject_duplicate_tx(false))]
+#[case::reverse_withdrawal_dispute(
+    "reverse-withdrawal-dispute",
+    ProcessOptions::new().withdrawal_disputes(WithdrawalDisputePolicy::Reverse)
+)]
+#[case::reverse_withdrawal_chargeback(
+    "reverse-withdrawal-chargeback",
+    ProcessOptions::new().withdrawal_disputes(WithdrawalDisputePolicy::Reverse)
+)]
+#[case::dispute_overdraft_allow_negative(
+    "dispute-overdraft-allow-negative",
+    ProcessOptions::new().dispute_overdraft(DisputeOverdraftPolicy::AllowNegative)
+)]
+#[case::dispute_overdraft_clamp_to_zero(
+    "dispute-overdraft-clamp-to-zero",
+    ProcessOptions::new().dispute_overdraft(DisputeOverdraftPolicy::ClampToZero)
+)]
+#[case::excess_precision_truncate(
+    "excess-precision-truncate",
+    ProcessOptions::new().excess_precision(ExcessPrecisionPolicy::Truncate)
+)]
+#[case::excess_precision_reject(
+    "excess-precision-reject",
+    ProcessOptions::new().excess_precision(ExcessPrecisionPolicy::Reject)
+)]
+#[case::two_pass("two-pass", ProcessOptions::new().two_pass(true))]
+fn with_options(#[case] name: &str, #[case] options: ProcessOptions) -> TestResult {
+    let input = format!("tests/test-cases/options/{name}.input.csv");
+    let expected = common::read_accounts(format!("tests/test-cases/options/{name}.output.csv"))?;
+
+    assert_ne!(
+        process(&input)?,
+        expected,
+        "default options must produce different output"
+    );
+    assert_eq!(process_with(&input, &options)?, expected);
+    Ok(())
+}
+
+#[test]
+fn reverse_withdrawal_resolve() -> TestResult {
+    // resolving the dispute leaves the account as if it never happened
+    // so the output matches the default policy
+    let input = "tests/test-cases/options/reverse-withdrawal-resolve.input.csv";
+    let expected =
+        common::read_accounts("tests/test-cases/options/reverse-withdrawal-resolve.output.csv")?;
+    let options = ProcessOptions::new().withdrawal_disputes(WithdrawalDisputePolicy::Reverse);
+
+    assert_eq!(process_with(input, &options)?, expected);
+    assert_eq!(process(input)?, expected);
+    Ok(())
+}
+
+#[test]
+fn allow_negative_available() -> TestResult {
+    let input = "tests/test-cases/options/dispute-overdraft-allow-negative.input.csv";
+    let expected = common::read_accounts(
+        "tests/test-cases/options/dispute-overdraft-allow-negative.output.csv",
+    )?;
+
+    let allowed = process_with(input, &ProcessOptions::new().allow_negative_available(true))?;
+    assert_eq!(allowed, expected);
+    assert_eq!(allowed[&1].amounts.available, (-5).into());
+
+    let rejected = process_with(
+        input,
+        &ProcessOptions::new().allow_negative_available(false),
+    )?;
+    assert_eq!(rejected, process(input)?);
+    assert_eq!(rejected[&1].amounts.available, 5.into());
+    Ok(())
+}
+
+#[test]
+fn allow_negative_available_config() {
+    let mut config = EngineConfig::default();
+    assert!(!config.allow_negative_available());
+    config.set_allow_negative_available(true);
+    assert!(config.allow_negative_available());
+    assert_eq!(
+        config.dispute_overdraft,
+        DisputeOverdraftPolicy::AllowNegative
+    );
+
+    // `false` replaces any policy, not only `AllowNegative`
+    config.dispute_overdraft = DisputeOverdraftPolicy::ClampToZero;
+    config.set_allow_negative_available(false);
+    assert!(!config.allow_negative_available());
+    assert_eq!(config.dispute_overdraft, DisputeOverdraftPolicy::Reject);
+
+    let options = ProcessOptions::new()
+        .dispute_overdraft(DisputeOverdraftPolicy::ClampToZero)
+        .allow_negative_available(false);
+    assert_eq!(
+        options.config().dispute_overdraft,
+        DisputeOverdraftPolicy::Reject
+    );
+}
+
+#[test]
+fn two_pass_strict_row() {
+    // errors of deferred records still name their row
+    let result = Engine::with_options(ProcessOptions::new().two_pass(true).strict(true))
+        .process_reader("type,client,tx,amount\ndispute,1,2,\ndeposit,1,1,1.0\n".as_bytes());
+    assert!(matches!(
+        result,
+        Err(Error::AtRow { row: 1, source, .. }) if matches!(*source, Error::TxNotFound(2))
+    ));
+}
+
+#[test]
+fn column_aliases_are_known() -> TestResult {
+    let input = "tests/test-cases/id-columns.input.csv";
+    let options = ProcessOptions::new().reject_unknown_columns(true);
+    assert_eq!(process_with(input, &options)?, process(input)?);
+    Ok(())
+}
+
+#[test]
+fn insolvent_after_dispute() -> TestResult {
+    // the dispute holds funds which have already been withdrawn
+    let input = "type,client,tx,amount\ndeposit,1,1,10.0\nwithdrawal,1,2,7.0\ndispute,1,1,\n";
+
+    let accounts = process_str(input)?;
+    assert!(accounts[&1].is_solvent());
+
+    let mut engine = Engine::with_options(ProcessOptions::new().allow_negative_available(true));
+    engine.process_reader(input.as_bytes())?;
+    let account = &engine.accounts()[&1];
+    assert_eq!(account.amounts.available, (-7).into());
+    assert!(!account.is_solvent());
+    assert!(account.validate().is_ok());
+    Ok(())
+}
+
+#[test]
+fn tab_separated() -> TestResult {
+    let options = ProcessOptions::new().delimiter(b'\t');
+    assert_eq!(
+        process_with("tests/test-cases/options/tab-separated.input.tsv", &options)?,
+        process("tests/test-cases/multiple-accounts.input.csv")?
+    );
+    Ok(())
+}
+
+#[test]
+fn no_headers() -> TestResult {
+    let input = "tests/test-cases/options/no-headers.input.csv";
+    let records = std::fs::read_to_string(input)?;
+    let with_headers = process_str(&format!("type,client,tx,amount\n{records}"))?;
+
+    let options = ProcessOptions::new().has_headers(false);
+    assert_eq!(process_with(input, &options)?, with_headers);
+    assert_eq!(
+        process_client_with(std::fs::File::open(input)?, 2, &options)?.as_ref(),
+        with_headers.get(&2)
+    );
+    // the first record is not mistaken for the header line
+    assert!(matches!(
+        without_path(process(input)),
+        Err(Error::MissingColumns(_))
+    ));
+    // there are no columns to validate
+    assert_eq!(
+        process_with(input, &options.reject_unknown_columns(true))?,
+        with_headers
+    );
+    Ok(())
+}
+
+#[test]
+fn strict() -> TestResult {
+    let input = "tests/test-cases/options/strict.input.csv";
+
+    // by default failed withdrawals are ignored
+    assert!(process(input).is_ok());
+
+    let result = without_path(process_with(input, &ProcessOptions::new().strict(true)));
+    assert!(matches!(
+        result,
+        Err(Error::AtRow { row: 2, source, .. }) if matches!(*source, Error::InsufficientFunds(2))
+    ));
+    Ok(())
+}
+
+#[test]
+fn strict_duplicate_tx() {
+    let result = without_path(process_with(
+        "tests/test-cases/duplicate-deposit.input.csv",
+        &ProcessOptions::new().strict(true),
+    ));
+    assert!(matches!(
+        result,
+        Err(Error::AtRow { row: 2, source, .. }) if matches!(*source, Error::DuplicateTx(1))
+    ));
+}
+
+#[test]
+fn strict_locked_account() {
+    let result = without_path(process_with(
+        "tests/test-cases/locked-deposit.input.csv",
+        &ProcessOptions::new().strict(true),
+    ));
+    let error = result.expect_err("deposit to a locked account to fail");
+    assert_eq!(
+        error.to_string(),
+        "Record 4: Transaction 2 failed - account 1 is locked"
+    );
+    assert!(matches!(
+        error,
+        Error::AtRow { row: 4, source, .. }
+            if matches!(*source, Error::AccountLocked { client: 1, tx: 2 })
+    ));
+}
+
+#[rstest]
+#[case::negative_deposit("negative-deposit", 1, 1)]
+#[case::negative_withdrawal("negative-withdrawal", 2, 2)]
+fn strict_negative_amount(#[case] name: &str, #[case] row: u64, #[case] tx: u32) {
+    let result = without_path(process_with(
+        format!("tests/test-cases/{name}.input.csv"),
+        &ProcessOptions::new().strict(true),
+    ));
+    assert!(matches!(
+        result,
+        Err(Error::AtRow { row: got_row, source, .. })
+            if got_row == row && matches!(*source, Error::NegativeAmount { tx: got, .. } if got == tx)
+    ));
+}
+
+#[test]
+fn strict_zero_deposit() -> TestResult {
+    let input = "tests/test-cases/zero-deposit.input.csv";
+    let expected = common::read_accounts("tests/test-cases/zero-deposit.output.csv")?;
+
+    // zero amounts are accepted in both modes
+    assert_eq!(
+        process_with(input, &ProcessOptions::new().strict(true))?,
+        expected
+    );
+    Ok(())
+}
+
+#[test]
+fn strict_excess_precision() {
+    let result = without_path(process_with(
+        "tests/test-cases/options/excess-precision-reject.input.csv",
+        &ProcessOptions::new()
+            .strict(true)
+            .excess_precision(ExcessPrecisionPolicy::Reject),
+    ));
+    // the first deposit has four decimal places and is accepted
+    assert!(matches!(
+        result,
+        Err(Error::AtRow { row: 2, source, .. }) if matches!(*source, Error::TooPrecise(2))
+    ));
+}
+
+#[test]
+fn max_transaction_amount() -> TestResult {
+    let input = "tests/test-cases/options/max-transaction-amount.input.csv";
+    let expected =
+        common::read_accounts("tests/test-cases/options/max-transaction-amount.output.csv")?;
+    let options = ProcessOptions::new().max_transaction_amount("1000000.0000".parse()?);
+
+    let mut engine = Engine::with_options(options.clone());
+    engine.process_file(input)?;
+    // the rejected deposit is not stored thus the dispute does not find it
+    assert!(engine.transaction(2).is_none());
+    assert_eq!(engine.into_accounts(), expected);
+
+    let result = without_path(process_with(input, &options.strict(true)));
+    assert!(matches!(
+        result,
+        Err(Error::AtRow { row: 2, source, .. })
+            if matches!(*source, Error::AmountTooLarge { tx: 2, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn skip_unknown_types() -> TestResult {
+    let input = "tests/test-cases/options/unknown-type.input.csv";
+    let expected = common::read_accounts("tests/test-cases/options/unknown-type.output.csv")?;
+    let options = ProcessOptions::new().skip_unknown_types(true);
+
+    assert!(matches!(
+        without_path(process(input)),
+        Err(Error::Parse { line: 3, .. })
+    ));
+    assert_eq!(process_with(input, &options)?, expected);
+
+    let result = without_path(process_with(input, &options.strict(true)));
+    assert!(matches!(
+        result,
+        Err(Error::UnknownRecordType { row: 2, value }) if value == "transfer"
+    ));
+    Ok(())
+}
+
+#[test]
+fn strict_unexpected_amount() {
+    let result = without_path(process_with(
+        "tests/test-cases/dispute-with-amount.input.csv",
+        &ProcessOptions::new().strict(true),
+    ));
+    assert!(matches!(
+        result,
+        Err(Error::AtRow { row: 3, source, .. }) if matches!(
+            *source,
+            Error::UnexpectedAmount {
+                tx: 1,
+                kind: RecordType::Dispute
+            }
+        )
+    ));
+}
+
+#[test]
+fn strict_client_mismatch() {
+    let result = without_path(process_with(
+        "tests/test-cases/client-mismatch.input.csv",
+        &ProcessOptions::new().strict(true),
+    ));
+    assert!(matches!(
+        result,
+        Err(Error::AtRow { row: 3, source, .. }) if matches!(
+            *source,
+            Error::ClientMismatch {
+                tx: 1,
+                expected: 1,
+                got: 2
+            }
+        )
+    ));
+}
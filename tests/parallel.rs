@@ -0,0 +1,109 @@
+use std::{fs::File, path::PathBuf};
+
+use rstest::rstest;
+use testresult::TestResult;
+use tx_engine::{
+    process, process_parallel, process_parallel_with, process_reader_with, process_str,
+    process_with, Error, ProcessOptions,
+};
+
+#[rstest]
+fn matches_serial(
+    #[files("tests/test-cases/*.input.csv")] path: PathBuf,
+    #[values(1, 2, 3)] threads: usize,
+) -> TestResult {
+    assert_eq!(
+        process_parallel(File::open(&path)?, threads)?,
+        process(&path)?,
+        "parallel processing must be equal with the serial one"
+    );
+    Ok(())
+}
+
+#[test]
+fn many_clients() -> TestResult {
+    let mut input = String::from("type,client,tx,amount\n");
+    for tx in 0..10_000 {
+        let client = tx % 37;
+        input += &format!("deposit,{client},{tx},1.5\n");
+        if tx % 3 == 0 {
+            input += &format!("dispute,{client},{tx},\n");
+        }
+        if tx % 9 == 0 {
+            input += &format!("chargeback,{client},{tx},\n");
+        }
+    }
+
+    assert_eq!(process_parallel(input.as_bytes(), 4)?, process_str(&input)?);
+    Ok(())
+}
+
+#[test]
+fn with_options() -> TestResult {
+    let options = ProcessOptions::new().delimiter(b'\t');
+    let input = File::open("tests/test-cases/options/tab-separated.input.tsv")?;
+    assert_eq!(
+        process_parallel_with(input, 2, &options)?,
+        process("tests/test-cases/multiple-accounts.input.csv")?
+    );
+
+    let input = "tests/test-cases/options/no-headers.input.csv";
+    let options = ProcessOptions::new().has_headers(false);
+    assert_eq!(
+        process_parallel_with(File::open(input)?, 2, &options)?,
+        process_with(input, &options)?
+    );
+    Ok(())
+}
+
+#[rstest]
+#[case::duplicate_deposit("deposit,1,1,1.0\ndeposit,2,1,5.0\n")]
+#[case::rejected_then_reused("withdrawal,1,1,1.0\ndeposit,2,1,5.0\ndispute,2,1,\n")]
+#[case::foreign_dispute("deposit,1,1,1.0\ndeposit,2,2,5.0\ndispute,2,1,\ndispute,1,1,\n")]
+#[case::after_fallback(
+    "deposit,1,1,1.0\ndeposit,2,1,5.0\ndeposit,3,3,2.0\ndispute,1,1,\nwithdrawal,3,4,0.5\n"
+)]
+fn shared_tx(
+    #[case] records: &str,
+    #[values(true, false)] reject_duplicate_tx: bool,
+) -> TestResult {
+    let input = format!("type,client,tx,amount\n{records}");
+    let options = ProcessOptions::new().reject_duplicate_tx(reject_duplicate_tx);
+    assert_eq!(
+        process_parallel_with(input.as_bytes(), 2, &options)?,
+        process_reader_with(input.as_bytes(), &options)?,
+        "parallel processing must be equal with the serial one"
+    );
+    Ok(())
+}
+
+#[test]
+fn zero_threads() -> TestResult {
+    let input = "type,client,tx,amount\ndeposit,1,1,1.0\n";
+    assert_eq!(process_parallel(input.as_bytes(), 0)?, process_str(input)?);
+    Ok(())
+}
+
+#[test]
+fn parse_error() {
+    let input = "type,client,tx,amount\ndeposit,1,1,1.0\ntransfer,1,2,1.0\n";
+    let result = process_parallel(input.as_bytes(), 2);
+    assert!(matches!(
+        result,
+        Err(Error::Parse {
+            line: 3,
+            row: 2,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn worker_error() {
+    let input = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,2,2,\n";
+    let result = process_parallel(input.as_bytes(), 2);
+    assert!(matches!(
+        result,
+        Err(Error::AtRow { row: 2, source, .. }) if matches!(*source, Error::DepositNoAmount(2))
+    ));
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "sqlite")]
+
+use rusqlite::Connection;
+use testresult::TestResult;
+use tx_engine::{process_sqlite, process_str, Error};
+
+/// Creates an in-memory database with a table of transactions.
+fn database(rows: &str) -> rusqlite::Result<Connection> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch(&format!(
+        "CREATE TABLE transactions (seq INTEGER, kind TEXT, client INTEGER, tx INTEGER, amount);
+         INSERT INTO transactions VALUES {rows};"
+    ))?;
+    Ok(conn)
+}
+
+#[test]
+fn matches_csv() -> TestResult {
+    let conn = database(
+        "(3, 'withdrawal', 1, 3, '0.5'), (1, 'deposit', 1, 1, '1.0'), (2, 'deposit', 2, 2, 2), \
+         (4, 'dispute', 2, 2, NULL), (5, 'deposit', 2, 5, 1.25)",
+    )?;
+    let accounts = process_sqlite(
+        &conn,
+        "SELECT kind, client, tx, amount FROM transactions ORDER BY seq",
+    )?;
+
+    assert_eq!(
+        accounts,
+        process_str(
+            "type,client,tx,amount\n\
+             deposit,1,1,1.0\ndeposit,2,2,2\nwithdrawal,1,3,0.5\ndispute,2,2,\ndeposit,2,5,1.25\n"
+        )?
+    );
+    Ok(())
+}
+
+#[test]
+fn invalid_type() -> TestResult {
+    let conn = database("(1, 'deposit', 1, 1, '1.0'), (2, 'transfer', 1, 2, '1.0')")?;
+    let result = process_sqlite(
+        &conn,
+        "SELECT kind, client, tx, amount FROM transactions ORDER BY seq",
+    );
+    assert!(matches!(
+        result,
+        Err(Error::InvalidField { row: 2, column: "type", value }) if value == "transfer"
+    ));
+    Ok(())
+}
+
+#[test]
+fn client_too_big() -> TestResult {
+    let conn = database("(1, 'deposit', 1, 1, '1.0'), (2, 'deposit', 70000, 2, '1.0')")?;
+    let result = process_sqlite(
+        &conn,
+        "SELECT kind, client, tx, amount FROM transactions ORDER BY seq",
+    );
+    assert!(matches!(
+        result,
+        Err(Error::InvalidField { row: 2, column: "client", value }) if value == "70000"
+    ));
+    Ok(())
+}